@@ -0,0 +1,145 @@
+use std::{
+    fmt, fs,
+    io::ErrorKind,
+    path::PathBuf,
+    process::{Command, Stdio},
+};
+
+use anyhow::{Context, Result};
+use regex::Regex;
+
+use crate::settings::FileContains;
+
+/// Check of system state before script is executed, similar to `Condition*` of systemd units.
+/// A value prefixed with `!` negates the check.
+#[derive(Debug)]
+pub enum Condition {
+    PathExists { path: PathBuf, negate: bool },
+    UnitActive { unit: String, negate: bool },
+    FileContains { path: PathBuf, pattern: Regex },
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let not = |negate: &bool| if *negate { "!" } else { "" };
+        match self {
+            Condition::PathExists { path, negate } => {
+                write!(f, "condition-path-exists={}{}", not(negate), path.display())
+            }
+            Condition::UnitActive { unit, negate } => {
+                write!(f, "condition-unit-active={}{unit}", not(negate))
+            }
+            Condition::FileContains { path, pattern } => write!(
+                f,
+                "condition-file-contains={}:{}",
+                path.display(),
+                pattern.as_str()
+            ),
+        }
+    }
+}
+
+/// Split `!` prefix from value of condition
+fn negation(value: &str) -> (&str, bool) {
+    match value.strip_prefix('!') {
+        Some(value) => (value, true),
+        None => (value, false),
+    }
+}
+
+impl Condition {
+    pub fn path_exists(value: &str) -> Self {
+        let (path, negate) = negation(value);
+        Condition::PathExists {
+            path: PathBuf::from(path),
+            negate,
+        }
+    }
+
+    pub fn unit_active(value: &str) -> Self {
+        let (unit, negate) = negation(value);
+        Condition::UnitActive {
+            unit: unit.to_string(),
+            negate,
+        }
+    }
+
+    pub fn file_contains(value: FileContains) -> Result<Self> {
+        Ok(Condition::FileContains {
+            pattern: Regex::new(&value.pattern)
+                .with_context(|| format!("Invalid pattern of `{}`", value.path))?,
+            path: PathBuf::from(value.path),
+        })
+    }
+
+    /// Does system state satisfy this condition?
+    pub fn is_met(&self) -> Result<bool> {
+        match self {
+            Condition::PathExists { path, negate } => Ok(path.exists() != *negate),
+            Condition::UnitActive { unit, negate } => {
+                let status = Command::new("systemctl")
+                    .args(["is-active", "--quiet", unit])
+                    .stdin(Stdio::null())
+                    .status()
+                    .with_context(|| format!("Failed to check if `{unit}` is active"))?;
+                Ok(status.success() != *negate)
+            }
+            Condition::FileContains { path, pattern } => match fs::read_to_string(path) {
+                Ok(content) => Ok(pattern.is_match(&content)),
+                Err(err) if err.kind() == ErrorKind::NotFound => Ok(false),
+                Err(err) => {
+                    Err(err).with_context(|| format!("Could not read `{}`", path.display()))
+                }
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn test_path_exists() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().display().to_string();
+        assert!(Condition::path_exists(&path).is_met().unwrap());
+        assert!(!Condition::path_exists(&format!("!{path}"))
+            .is_met()
+            .unwrap());
+
+        let missing = temp_dir.path().join("missing").display().to_string();
+        assert!(!Condition::path_exists(&missing).is_met().unwrap());
+        assert!(Condition::path_exists(&format!("!{missing}"))
+            .is_met()
+            .unwrap());
+    }
+
+    #[test]
+    fn test_file_contains() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("power-state");
+        fs::write(&path, "state: on\n").unwrap();
+
+        let condition = |pattern: &str| {
+            Condition::file_contains(FileContains {
+                path: path.display().to_string(),
+                pattern: pattern.to_string(),
+            })
+            .unwrap()
+        };
+        assert!(condition("(?m)^state: on$").is_met().unwrap());
+        assert!(!condition("(?m)^state: off$").is_met().unwrap());
+
+        fs::remove_file(&path).unwrap();
+        assert!(!condition("(?m)^state: on$").is_met().unwrap());
+
+        assert!(Condition::file_contains(FileContains {
+            path: path.display().to_string(),
+            pattern: "(".to_string(),
+        })
+        .is_err());
+    }
+}
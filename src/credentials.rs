@@ -0,0 +1,346 @@
+use std::{
+    ffi::{CStr, CString},
+    io,
+    os::unix::{fs::MetadataExt, process::CommandExt},
+    path::{Path, PathBuf},
+    process::Command,
+    ptr,
+};
+
+use anyhow::{bail, Context, Result};
+
+/// Size of buffer for strings of passwd and group entries
+const ENTRY_BUFFER_SIZE: usize = 16384;
+
+/// User, group and supplementary groups which script is executed as
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Credentials {
+    uid: Option<libc::uid_t>,
+    gid: Option<libc::gid_t>,
+    groups: Option<Vec<libc::gid_t>>,
+    /// Passwd entry of user, for `HOME`, `USER` and `LOGNAME` of script
+    user: Option<User>,
+}
+
+/// Passwd entry of user
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct User {
+    name: CString,
+    uid: libc::uid_t,
+    gid: libc::gid_t,
+    home: PathBuf,
+}
+
+impl Credentials {
+    /// Resolve names or numeric IDs of user and groups, `None` if none of them is set.
+    /// Primary group and supplementary groups of user are used unless they are set.
+    pub fn new(
+        user: Option<&str>,
+        group: Option<&str>,
+        supplementary_groups: Option<&[String]>,
+    ) -> Result<Option<Self>> {
+        if user.is_none() && group.is_none() && supplementary_groups.is_none() {
+            return Ok(None);
+        }
+
+        let user = user.map(lookup_user).transpose()?;
+        let gid = match group {
+            Some(group) => Some(lookup_group(group)?),
+            None => user.as_ref().map(|user| user.gid),
+        };
+        let groups = match (supplementary_groups, &user) {
+            (Some(groups), _) => Some(
+                groups
+                    .iter()
+                    .map(|group| lookup_group(group))
+                    .collect::<Result<Vec<_>>>()?,
+            ),
+            (None, Some(user)) => Some(group_list(&user.name, gid.unwrap_or_default())?),
+            (None, None) => None,
+        };
+
+        Ok(Some(Self {
+            uid: user.as_ref().map(|user| user.uid),
+            gid,
+            groups,
+            user,
+        }))
+    }
+
+    /// Verify if script can be read and executed with these credentials.
+    /// Nothing is checked if script is still executed as root.
+    pub fn check_executable(&self, path: &Path) -> Result<()> {
+        let Some(uid) = self.uid.filter(|uid| *uid != 0) else {
+            return Ok(());
+        };
+
+        let metadata = path
+            .metadata()
+            .with_context(|| format!("Could not get metadata of `{}`", path.display()))?;
+        let in_group = |gid: libc::gid_t| {
+            self.gid == Some(gid)
+                || self
+                    .groups
+                    .as_ref()
+                    .is_some_and(|groups| groups.contains(&gid))
+        };
+        let mode = if metadata.uid() == uid {
+            metadata.mode() >> 6
+        } else if in_group(metadata.gid()) {
+            metadata.mode() >> 3
+        } else {
+            metadata.mode()
+        };
+
+        // Script interpreter needs to read it as well
+        if mode & 0o5 != 0o5 {
+            bail!(
+                "`{}` is not readable and executable by uid {uid}",
+                path.display()
+            );
+        }
+        Ok(())
+    }
+
+    /// Switch to these credentials in child process before script is executed.
+    pub fn apply(&self, command: &mut Command) {
+        if let Some(user) = &self.user {
+            let name = user.name.to_string_lossy();
+            command
+                .env("HOME", &user.home)
+                .env("USER", name.as_ref())
+                .env("LOGNAME", name.as_ref());
+        }
+
+        let Credentials {
+            uid, gid, groups, ..
+        } = self.clone();
+
+        // SAFETY: the closure runs in child process after fork, and only calls
+        // async-signal-safe functions. Names are resolved before fork.
+        unsafe {
+            command.pre_exec(move || {
+                // Supplementary groups and group must be changed while still privileged.
+                if let Some(groups) = &groups {
+                    if libc::setgroups(groups.len() as _, groups.as_ptr()) != 0 {
+                        return Err(io::Error::last_os_error());
+                    }
+                }
+                if let Some(gid) = gid {
+                    if libc::setgid(gid) != 0 {
+                        return Err(io::Error::last_os_error());
+                    }
+                }
+                if let Some(uid) = uid {
+                    if libc::setuid(uid) != 0 {
+                        return Err(io::Error::last_os_error());
+                    }
+                }
+                Ok(())
+            });
+        }
+    }
+}
+
+/// Passwd entry of user name or numeric uid
+fn lookup_user(user: &str) -> Result<User> {
+    // SAFETY: passwd is plain old data, all-zero is a valid value.
+    let mut passwd: libc::passwd = unsafe { std::mem::zeroed() };
+    let mut buffer = vec![0 as libc::c_char; ENTRY_BUFFER_SIZE];
+    let mut result: *mut libc::passwd = ptr::null_mut();
+
+    // SAFETY: all pointers are valid for the call, and buffer length is correct.
+    let ret = match user.parse::<libc::uid_t>() {
+        Ok(uid) => unsafe {
+            libc::getpwuid_r(
+                uid,
+                &mut passwd,
+                buffer.as_mut_ptr(),
+                buffer.len(),
+                &mut result,
+            )
+        },
+        Err(_) => {
+            let name = CString::new(user).with_context(|| format!("Invalid user `{user}`"))?;
+            unsafe {
+                libc::getpwnam_r(
+                    name.as_ptr(),
+                    &mut passwd,
+                    buffer.as_mut_ptr(),
+                    buffer.len(),
+                    &mut result,
+                )
+            }
+        }
+    };
+    if ret != 0 {
+        return Err(io::Error::from_raw_os_error(ret))
+            .with_context(|| format!("Failed to look up user `{user}`"));
+    }
+    if result.is_null() {
+        bail!("Unknown user `{user}`");
+    }
+
+    // SAFETY: pw_name and pw_dir point to NUL-terminated strings inside buffer.
+    let (name, home) = unsafe {
+        (
+            CStr::from_ptr(passwd.pw_name).to_owned(),
+            CStr::from_ptr(passwd.pw_dir),
+        )
+    };
+    Ok(User {
+        name,
+        uid: passwd.pw_uid,
+        gid: passwd.pw_gid,
+        home: PathBuf::from(home.to_string_lossy().into_owned()),
+    })
+}
+
+/// gid of group name or numeric gid
+fn lookup_group(group: &str) -> Result<libc::gid_t> {
+    if let Ok(gid) = group.parse::<libc::gid_t>() {
+        return Ok(gid);
+    }
+
+    let name = CString::new(group).with_context(|| format!("Invalid group `{group}`"))?;
+    // SAFETY: group is plain old data, all-zero is a valid value.
+    let mut entry: libc::group = unsafe { std::mem::zeroed() };
+    let mut buffer = vec![0 as libc::c_char; ENTRY_BUFFER_SIZE];
+    let mut result: *mut libc::group = ptr::null_mut();
+
+    // SAFETY: all pointers are valid for the call, and buffer length is correct.
+    let ret = unsafe {
+        libc::getgrnam_r(
+            name.as_ptr(),
+            &mut entry,
+            buffer.as_mut_ptr(),
+            buffer.len(),
+            &mut result,
+        )
+    };
+    if ret != 0 {
+        return Err(io::Error::from_raw_os_error(ret))
+            .with_context(|| format!("Failed to look up group `{group}`"));
+    }
+    if result.is_null() {
+        bail!("Unknown group `{group}`");
+    }
+    Ok(entry.gr_gid)
+}
+
+/// Supplementary groups of user, including `gid`
+fn group_list(user: &CStr, gid: libc::gid_t) -> Result<Vec<libc::gid_t>> {
+    let mut groups: Vec<libc::gid_t> = vec![0; 64];
+    loop {
+        let mut count = groups.len() as libc::c_int;
+        // SAFETY: groups has room for `count` entries.
+        let ret =
+            unsafe { libc::getgrouplist(user.as_ptr(), gid, groups.as_mut_ptr(), &mut count) };
+        if ret >= 0 {
+            groups.truncate(count as usize);
+            return Ok(groups);
+        }
+        if count as usize <= groups.len() {
+            bail!(
+                "Failed to get supplementary groups of `{}`",
+                user.to_string_lossy()
+            );
+        }
+        groups.resize(count as usize, 0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{ffi::OsStr, fs, os::unix::fs::PermissionsExt};
+
+    use tempfile::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn test_lookup() {
+        let root = lookup_user("root").unwrap();
+        assert_eq!(
+            (root.name.to_str().unwrap(), root.uid, root.gid),
+            ("root", 0, 0)
+        );
+        assert_eq!(root.home, Path::new("/root"));
+        assert_eq!(lookup_user("0").unwrap().uid, 0);
+        assert!(lookup_user("no-such-user-jnb").is_err());
+
+        assert_eq!(lookup_group("root").unwrap(), 0);
+        assert_eq!(lookup_group("12345").unwrap(), 12345);
+        assert!(lookup_group("no-such-group-jnb").is_err());
+    }
+
+    #[test]
+    fn test_credentials() {
+        assert_eq!(Credentials::new(None, None, None).unwrap(), None);
+
+        let credentials = Credentials::new(Some("root"), None, None).unwrap().unwrap();
+        assert_eq!(credentials.uid, Some(0));
+        assert_eq!(credentials.gid, Some(0));
+        assert!(credentials.groups.unwrap().contains(&0));
+
+        let credentials = Credentials::new(
+            Some("root"),
+            Some("12345"),
+            Some(&["root".to_string(), "23456".to_string()]),
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(credentials.gid, Some(12345));
+        assert_eq!(credentials.groups, Some(vec![0, 23456]));
+
+        assert!(Credentials::new(Some("no-such-user-jnb"), None, None).is_err());
+
+        // Login environment of the user
+        let mut command = Command::new("/bin/true");
+        Credentials::new(Some("root"), None, None)
+            .unwrap()
+            .unwrap()
+            .apply(&mut command);
+        let envs: Vec<(&OsStr, Option<&OsStr>)> = command.get_envs().collect();
+        assert!(envs.contains(&(OsStr::new("HOME"), Some(OsStr::new("/root")))));
+        assert!(envs.contains(&(OsStr::new("USER"), Some(OsStr::new("root")))));
+        assert!(envs.contains(&(OsStr::new("LOGNAME"), Some(OsStr::new("root")))));
+    }
+
+    #[test]
+    fn test_check_executable() {
+        let temp_dir = TempDir::new().unwrap();
+        let script = temp_dir.path().join("script");
+        fs::write(&script, "#!/bin/sh\n").unwrap();
+        let file_gid = script.metadata().unwrap().gid();
+        let set_mode = |mode: u32| {
+            fs::set_permissions(&script, fs::Permissions::from_mode(mode)).unwrap();
+        };
+        let nobody = |gid: libc::gid_t| Credentials {
+            uid: Some(65534),
+            gid: Some(gid),
+            groups: Some(Vec::new()),
+            user: None,
+        };
+
+        set_mode(0o755);
+        assert!(nobody(65534).check_executable(&script).is_ok());
+
+        set_mode(0o750);
+        assert!(nobody(65534).check_executable(&script).is_err());
+        assert!(nobody(file_gid).check_executable(&script).is_ok());
+
+        set_mode(0o711);
+        assert!(nobody(65534).check_executable(&script).is_err());
+
+        // Still executed as root
+        let group_only = Credentials {
+            uid: None,
+            ..nobody(65534)
+        };
+        assert!(group_only.check_executable(&script).is_ok());
+        assert!(nobody(65534)
+            .check_executable(&temp_dir.path().join("missing"))
+            .is_err());
+    }
+}
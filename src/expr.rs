@@ -0,0 +1,370 @@
+//! Boolean expression on journal fields of `when` setting, e.g.
+//! `(PRIORITY<=3 OR MESSAGE matches 'disk .* full') AND NOT _COMM=foo`
+//!
+//! - `AND`, `OR`, `NOT` (or `&&`, `||`, `!`) and parentheses, in order of precedence `NOT`, `AND`, `OR`.
+//! - `FIELD=value`, `FIELD!=value` compare text.
+//! - `FIELD matches regex` (or `~`), `FIELD !~ regex` match regular expression.
+//! - `FIELD<number`, `<=`, `>`, `>=` compare numbers. Field which is not a number does not match.
+//! - Value containing spaces, parentheses or operators must be quoted with `'` or `"`.
+//! - Comparison of missing field is false, except `!=` and `!~` which are true.
+
+use std::collections::BTreeMap;
+
+use anyhow::{bail, Context, Result};
+use regex::Regex;
+use serde::Deserialize;
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    LParen,
+    RParen,
+    And,
+    Or,
+    Not,
+    Op(Op),
+    Word(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Eq,
+    Ne,
+    Match,
+    NotMatch,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug)]
+enum Operand {
+    Text(String),
+    Regex(Regex),
+    Number(f64),
+}
+
+#[derive(Debug)]
+enum Node {
+    And(Box<Node>, Box<Node>),
+    Or(Box<Node>, Box<Node>),
+    Not(Box<Node>),
+    Compare {
+        field: String,
+        op: Op,
+        operand: Operand,
+    },
+}
+
+/// Parsed and validated expression
+#[derive(Debug, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Expr(Node);
+
+impl TryFrom<String> for Expr {
+    type Error = anyhow::Error;
+
+    fn try_from(source: String) -> Result<Self> {
+        Expr::parse(&source)
+    }
+}
+
+impl Expr {
+    pub fn parse(source: &str) -> Result<Self> {
+        let tokens = tokenize(source).with_context(|| format!("Invalid expression `{source}`"))?;
+        let mut parser = Parser { tokens, pos: 0 };
+        let node = parser
+            .or()
+            .and_then(|node| match parser.tokens.get(parser.pos) {
+                None => Ok(node),
+                Some(token) => bail!("Unexpected {token:?}"),
+            })
+            .with_context(|| format!("Invalid expression `{source}`"))?;
+        Ok(Expr(node))
+    }
+
+    /// Does journal entry satisfy this expression?
+    pub fn eval(&self, entry: &BTreeMap<String, String>) -> bool {
+        self.0.eval(entry)
+    }
+}
+
+impl Node {
+    fn eval(&self, entry: &BTreeMap<String, String>) -> bool {
+        match self {
+            Node::And(lhs, rhs) => lhs.eval(entry) && rhs.eval(entry),
+            Node::Or(lhs, rhs) => lhs.eval(entry) || rhs.eval(entry),
+            Node::Not(node) => !node.eval(entry),
+            Node::Compare { field, op, operand } => {
+                let value = entry.get(field);
+                match (op, operand) {
+                    (Op::Eq, Operand::Text(text)) => value == Some(text),
+                    (Op::Ne, Operand::Text(text)) => value != Some(text),
+                    (Op::Match, Operand::Regex(regex)) => value.is_some_and(|v| regex.is_match(v)),
+                    (Op::NotMatch, Operand::Regex(regex)) => {
+                        !value.is_some_and(|v| regex.is_match(v))
+                    }
+                    (_, Operand::Number(number)) => {
+                        let Some(value) = value.and_then(|v| v.trim().parse::<f64>().ok()) else {
+                            return false;
+                        };
+                        match op {
+                            Op::Lt => value < *number,
+                            Op::Le => value <= *number,
+                            Op::Gt => value > *number,
+                            Op::Ge => value >= *number,
+                            _ => false,
+                        }
+                    }
+                    _ => false,
+                }
+            }
+        }
+    }
+}
+
+/// Characters that end a bare word
+fn is_special(c: char) -> bool {
+    c.is_whitespace() || "()=!<>~&|'\"".contains(c)
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>> {
+    let mut tokens: Vec<Token> = Vec::new();
+    let mut chars = source.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+
+        chars.next();
+        let next = chars.peek().copied();
+        let token = match (c, next) {
+            ('(', _) => Token::LParen,
+            (')', _) => Token::RParen,
+            ('&', Some('&')) => {
+                chars.next();
+                Token::And
+            }
+            ('|', Some('|')) => {
+                chars.next();
+                Token::Or
+            }
+            ('!', Some('=')) => {
+                chars.next();
+                Token::Op(Op::Ne)
+            }
+            ('!', Some('~')) => {
+                chars.next();
+                Token::Op(Op::NotMatch)
+            }
+            ('!', _) => Token::Not,
+            ('<', Some('=')) => {
+                chars.next();
+                Token::Op(Op::Le)
+            }
+            ('>', Some('=')) => {
+                chars.next();
+                Token::Op(Op::Ge)
+            }
+            ('<', _) => Token::Op(Op::Lt),
+            ('>', _) => Token::Op(Op::Gt),
+            ('=', _) => Token::Op(Op::Eq),
+            ('~', _) => Token::Op(Op::Match),
+            ('\'' | '"', _) => {
+                let mut word = String::new();
+                loop {
+                    match chars.next() {
+                        Some('\\') if chars.peek() == Some(&c) => word.push(chars.next().unwrap()),
+                        Some(quote) if quote == c => break,
+                        Some(ch) => word.push(ch),
+                        None => bail!("Unterminated quote {c}"),
+                    }
+                }
+                Token::Word(word)
+            }
+            ('&' | '|', _) => bail!("Unexpected `{c}`"),
+            _ => {
+                let mut word = c.to_string();
+                while let Some(&ch) = chars.peek() {
+                    if is_special(ch) {
+                        break;
+                    }
+                    word.push(ch);
+                    chars.next();
+                }
+                match word.to_ascii_lowercase().as_str() {
+                    "and" => Token::And,
+                    "or" => Token::Or,
+                    "not" => Token::Not,
+                    "matches" => Token::Op(Op::Match),
+                    _ => Token::Word(word),
+                }
+            }
+        };
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
+/// Recursive descent parser, one function per precedence level
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn next(&mut self) -> Option<&Token> {
+        let token = self.tokens.get(self.pos);
+        self.pos += 1;
+        token
+    }
+
+    /// Skip the next token if it is the expected one
+    fn eat(&mut self, expected: &Token) -> bool {
+        let found = self.tokens.get(self.pos) == Some(expected);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn or(&mut self) -> Result<Node> {
+        let mut node = self.and()?;
+        while self.eat(&Token::Or) {
+            node = Node::Or(Box::new(node), Box::new(self.and()?));
+        }
+        Ok(node)
+    }
+
+    fn and(&mut self) -> Result<Node> {
+        let mut node = self.not()?;
+        while self.eat(&Token::And) {
+            node = Node::And(Box::new(node), Box::new(self.not()?));
+        }
+        Ok(node)
+    }
+
+    fn not(&mut self) -> Result<Node> {
+        if self.eat(&Token::Not) {
+            return Ok(Node::Not(Box::new(self.not()?)));
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<Node> {
+        let field = match self.next() {
+            Some(Token::LParen) => {
+                let node = self.or()?;
+                match self.next() {
+                    Some(Token::RParen) => return Ok(node),
+                    Some(token) => bail!("Expect `)` but found {token:?}"),
+                    None => bail!("Expect `)` but found the end"),
+                }
+            }
+            Some(Token::Word(field)) => field.clone(),
+            Some(token) => bail!("Expect field name but found {token:?}"),
+            None => bail!("Expect field name but found the end"),
+        };
+
+        let op = match self.next() {
+            Some(Token::Op(op)) => *op,
+            Some(token) => bail!("Expect operator after `{field}` but found {token:?}"),
+            None => bail!("Expect operator after `{field}` but found the end"),
+        };
+
+        let value = match self.next() {
+            Some(Token::Word(value)) => value.clone(),
+            Some(token) => bail!("Expect value of `{field}` but found {token:?}"),
+            None => bail!("Expect value of `{field}` but found the end"),
+        };
+
+        let operand = match op {
+            Op::Eq | Op::Ne => Operand::Text(value),
+            Op::Match | Op::NotMatch => Operand::Regex(
+                Regex::new(&value)
+                    .with_context(|| format!("Invalid regular expression of `{field}`"))?,
+            ),
+            Op::Lt | Op::Le | Op::Gt | Op::Ge => Operand::Number(
+                value
+                    .parse()
+                    .with_context(|| format!("`{value}` of `{field}` is not a number"))?,
+            ),
+        };
+
+        Ok(Node::Compare { field, op, operand })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(fields: &[(&str, &str)]) -> BTreeMap<String, String> {
+        fields
+            .iter()
+            .map(|(key, val)| (key.to_string(), val.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_tokenize() {
+        assert_eq!(
+            tokenize(r#"(PRIORITY<=3 || MESSAGE ~ 'a \'b\'') and not _COMM!="foo""#).unwrap(),
+            vec![
+                Token::LParen,
+                Token::Word("PRIORITY".to_string()),
+                Token::Op(Op::Le),
+                Token::Word("3".to_string()),
+                Token::Or,
+                Token::Word("MESSAGE".to_string()),
+                Token::Op(Op::Match),
+                Token::Word("a 'b'".to_string()),
+                Token::RParen,
+                Token::And,
+                Token::Not,
+                Token::Word("_COMM".to_string()),
+                Token::Op(Op::Ne),
+                Token::Word("foo".to_string()),
+            ]
+        );
+        assert!(tokenize("MESSAGE='unterminated").is_err());
+        assert!(tokenize("A=1 & B=2").is_err());
+    }
+
+    #[test]
+    fn test_eval() {
+        let expr = Expr::parse("(PRIORITY<=3 OR MESSAGE matches 'disk .* full') AND NOT _COMM=foo")
+            .unwrap();
+        assert!(expr.eval(&entry(&[("PRIORITY", "2"), ("_COMM", "bar")])));
+        assert!(expr.eval(&entry(&[("PRIORITY", "2")])));
+        assert!(expr.eval(&entry(&[
+            ("PRIORITY", "6"),
+            ("MESSAGE", "disk sda is full")
+        ])));
+        assert!(!expr.eval(&entry(&[("PRIORITY", "6"), ("MESSAGE", "ok")])));
+        assert!(!expr.eval(&entry(&[("PRIORITY", "2"), ("_COMM", "foo")])));
+        assert!(!expr.eval(&entry(&[("PRIORITY", "high")])));
+
+        // AND binds tighter than OR
+        let expr = Expr::parse("A=1 || B=1 && C=1").unwrap();
+        assert!(expr.eval(&entry(&[("A", "1")])));
+        assert!(!expr.eval(&entry(&[("B", "1")])));
+
+        // Negated comparison of missing field is true
+        assert!(Expr::parse("A!=1").unwrap().eval(&entry(&[])));
+        assert!(Expr::parse("A !~ '^1'").unwrap().eval(&entry(&[])));
+        assert!(!Expr::parse("A=1").unwrap().eval(&entry(&[])));
+    }
+
+    #[test]
+    fn test_parse_error() {
+        assert!(Expr::parse("").is_err());
+        assert!(Expr::parse("PRIORITY").is_err());
+        assert!(Expr::parse("PRIORITY<=high").is_err());
+        assert!(Expr::parse("MESSAGE matches '('").is_err());
+        assert!(Expr::parse("(A=1").is_err());
+        assert!(Expr::parse("A=1 B=2").is_err());
+        assert!(Expr::parse("A=1 AND").is_err());
+    }
+}
@@ -1,46 +1,150 @@
 use std::{
-    sync::mpsc::{channel, RecvError, Sender},
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Condvar, Mutex, PoisonError},
     thread,
 };
 
 use anyhow::{Context, Result};
-use tracing::{error, warn};
+use tracing::warn;
 
 use crate::script::Script;
 
+/// Number of scripts executed at the same time if global `max-concurrent` is not set.
+/// Scripts are executed one at a time by default.
+pub const DEFAULT_MAX_CONCURRENT: usize = 1;
+
+/// Script waiting in launcher's queue
+#[derive(Debug)]
+struct Job {
+    /// Name of event which the script belongs to
+    name: String,
+    /// Maximum number of running scripts of the event
+    max_concurrent: Option<usize>,
+    script: Script,
+}
+
+#[derive(Debug, Default)]
+struct Queue {
+    jobs: VecDeque<Job>,
+    /// Number of running scripts, keyed by event name
+    running: HashMap<String, usize>,
+}
+
+impl Queue {
+    /// Take the first job whose event has not reached its `max-concurrent`, and count it as running.
+    fn take(&mut self) -> Option<Job> {
+        let position = self.jobs.iter().position(|job| {
+            job.max_concurrent
+                .is_none_or(|max| self.running.get(&job.name).copied().unwrap_or(0) < max)
+        })?;
+        let job = self.jobs.remove(position)?;
+        *self.running.entry(job.name.clone()).or_default() += 1;
+        Some(job)
+    }
+
+    fn finish(&mut self, name: &str) {
+        if let Some(running) = self.running.get_mut(name) {
+            *running -= 1;
+            if *running == 0 {
+                self.running.remove(name);
+            }
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct Launcher {
-    tx: Sender<Box<Script>>,
+    queue: Arc<(Mutex<Queue>, Condvar)>,
 }
 
 impl Launcher {
-    pub fn new() -> Result<Self> {
-        let (tx, rx) = channel::<Box<Script>>();
-
-        thread::Builder::new()
-            .name("script launcher".to_string())
-            .spawn(move || loop {
-                match rx.recv() {
-                    Ok(script) => {
-                        if let Err(err) = script.run() {
+    /// Start `max_concurrent` workers which execute scripts in the queue.
+    pub fn new(max_concurrent: usize) -> Result<Self> {
+        let queue = Arc::new((Mutex::new(Queue::default()), Condvar::new()));
+
+        for worker in 0..max_concurrent {
+            let queue = Arc::clone(&queue);
+            thread::Builder::new()
+                .name(format!("script launcher {worker}"))
+                .spawn(move || {
+                    let (jobs, ready) = &*queue;
+                    loop {
+                        let job = {
+                            let mut jobs = jobs.lock().unwrap_or_else(PoisonError::into_inner);
+                            loop {
+                                if let Some(job) = jobs.take() {
+                                    break job;
+                                }
+                                jobs = ready.wait(jobs).unwrap_or_else(PoisonError::into_inner);
+                            }
+                        };
+
+                        if let Err(err) = job.script.run() {
                             warn!("{err:#}");
                         }
+
+                        jobs.lock()
+                            .unwrap_or_else(PoisonError::into_inner)
+                            .finish(&job.name);
+                        ready.notify_all();
                     }
-                    Err(RecvError {}) => {
-                        error!("Failed to receive script");
-                    }
-                };
-            })
-            .context("Could not create script launcher thread")?;
+                })
+                .context("Could not create script launcher thread")?;
+        }
 
-        Ok(Launcher { tx })
+        Ok(Launcher { queue })
     }
 
-    /// Add a script to execute queue
-    pub fn add(&self, script: Script) -> Result<()> {
-        self.tx
-            .send(Box::new(script))
-            .context("Failed to send a script to launcher channel")?;
+    /// Add a script of event to execute queue
+    pub fn add(&self, name: &str, max_concurrent: Option<usize>, script: Script) -> Result<()> {
+        let (jobs, ready) = &*self.queue;
+        jobs.lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .jobs
+            .push_back(Job {
+                name: name.to_string(),
+                max_concurrent,
+                script,
+            });
+        ready.notify_one();
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+
+    fn job(name: &str, max_concurrent: Option<usize>) -> Job {
+        Job {
+            name: name.to_string(),
+            max_concurrent,
+            script: Script::new(Path::new("/bin/true"), None, false).unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_queue_max_concurrent() {
+        let mut queue = Queue::default();
+        queue.jobs.push_back(job("reboot", Some(1)));
+        queue.jobs.push_back(job("reboot", Some(1)));
+        queue.jobs.push_back(job("notify", None));
+
+        // The second job of `reboot` waits until the first one is finished,
+        // without blocking jobs of other events.
+        assert_eq!(queue.take().unwrap().name, "reboot");
+        assert_eq!(queue.take().unwrap().name, "notify");
+        assert!(queue.take().is_none());
+        assert_eq!(queue.jobs.len(), 1);
+
+        queue.finish("reboot");
+        assert_eq!(queue.take().unwrap().name, "reboot");
+        assert!(queue.take().is_none());
+
+        queue.finish("reboot");
+        queue.finish("notify");
+        assert!(queue.running.is_empty());
+    }
+}
@@ -1,4 +1,7 @@
 pub mod args;
+pub mod condition;
+pub mod credentials;
+pub mod expr;
 pub mod launcher;
 pub mod monitor;
 pub mod script;
@@ -1,48 +1,1193 @@
 use std::{
-    collections::BTreeMap,
-    path::PathBuf,
-    sync::OnceLock,
-    time::{Duration, Instant},
+    collections::{BTreeMap, VecDeque},
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 
 use anyhow::{bail, Context, Result};
-use regex::RegexSet;
+use chrono::{Datelike, Local, NaiveDateTime, NaiveTime, Timelike};
+use regex::{Regex, RegexSet};
 use systemd::{daemon, journal, Journal};
 use tracing::{debug, error, info, warn};
 
 use crate::{
-    launcher::Launcher,
-    script::{EnvVar, Script},
-    settings::Settings,
+    condition::Condition,
+    credentials::Credentials,
+    expr::Expr,
+    launcher::{Launcher, DEFAULT_MAX_CONCURRENT},
+    script::{self, EnvVar, Script, DEFAULT_KILL_GRACE},
+    settings::{
+        self, Aggregate, Backoff, CompareOp, CompareValue, EventKind, Global, MaxRuns, Normalize,
+        OffSchedule, Settings, Threshold, ThrottleMode,
+    },
 };
 
+/// Captured groups of regular expression keyed by group names and numbers
+type Captures = BTreeMap<String, String>;
+
+/// Journal entry and its timestamp
+#[derive(Debug, Clone)]
+struct Entry {
+    fields: BTreeMap<String, String>,
+    realtime: SystemTime,
+    /// Time since boot, if the entry is from the current boot
+    uptime: Option<Duration>,
+}
+
+impl Entry {
+    pub fn message(&self) -> &str {
+        self.fields.get("MESSAGE").map(String::as_str).unwrap_or("")
+    }
+}
+
+/// Time since boot, the same clock as `__MONOTONIC_TIMESTAMP` of journal
+fn uptime() -> Option<Duration> {
+    let mut now = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    // SAFETY: `now` is valid for writes.
+    if unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut now) } != 0 {
+        return None;
+    }
+    Some(Duration::new(now.tv_sec as u64, now.tv_nsec as u32))
+}
+
+/// Microseconds since the epoch, same as `__REALTIME_TIMESTAMP` of journal
+fn realtime_usec(timestamp: SystemTime) -> u128 {
+    timestamp
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_micros()
+}
+
+/// Maximum number of distinct throttle keys tracked by each event
+const THROTTLE_KEYS: usize = 1024;
+
+/// Maximum number of fingerprints tracked by monitor
+const FINGERPRINTS: usize = 4096;
+
 struct Event {
     pub name: String,
-    pub msg_filter: String,
+    pub kind: EventKind,
+    /// Journal field filters. Values of the same field are OR-ed, different fields are AND-ed.
+    filters: BTreeMap<String, Vec<String>>,
+    pub message: Regex,
+    /// Regular expressions of journal fields, all of them must match.
+    fields: Vec<(String, Regex)>,
+    /// Suppress match if log message also matches one of these.
+    exclude: RegexSet,
+    /// Number of matches suppressed by exclude patterns
+    excluded: u64,
+    /// Numeric comparisons on captured groups or journal fields
+    compare: Vec<Compare>,
+    /// Expression on journal fields
+    when: Option<Expr>,
     next_watch_delay: Option<Duration>,
-    last_found: Option<Instant>,
+    /// Capture group name or journal field name whose values are throttled separately
+    throttle_key: Option<String>,
+    /// Next watch delay of the event, keyed by value of `throttle_key`
+    last_found: BTreeMap<String, LastFound>,
+    backoff: Option<Backoff>,
+    throttle_mode: ThrottleMode,
+    /// The latest matches suppressed by trailing edge throttle, keyed by value of `throttle_key`
+    debounced: BTreeMap<String, Debounced>,
+    threshold: Option<Threshold>,
+    /// Timestamps of matches inside threshold window
+    recent: VecDeque<SystemTime>,
+    /// Expected interval between messages of absence event
+    expect_every: Option<Duration>,
+    /// The latest matching entry of absence event
+    last_seen: Option<Entry>,
+    /// When absence event fires unless a matching entry arrives
+    expected_by: Option<Instant>,
+    sequence: Option<Sequence>,
+    delay: Option<Duration>,
+    /// Recovery message that drops postponed script executions
+    cancel_on: Option<Regex>,
+    /// Capture group name shared by message and recovery message
+    cancel_key: Option<String>,
+    /// Postponed script executions keyed by value of `cancel_key`
+    delayed: BTreeMap<String, Delayed>,
+    /// Recovery message that resolves open event
+    resolve_on: Option<Regex>,
+    /// Script to run when open event is resolved
+    on_resolve: Option<PathBuf>,
+    /// Triggering entry and when the event is opened
+    opened: Option<(Entry, Instant)>,
+    aggregate: Option<Aggregate>,
+    /// Matches collected inside aggregate window
+    aggregated: Option<Aggregated>,
+    /// Script runs limited by `max-runs`
+    quota: Option<Quota>,
+    /// Number of firings suppressed by `max-runs` since the last run
+    suppressed: u64,
+    dedup: Option<Dedup>,
+    /// Skip matches within this time since daemon start
+    ignore_first: Option<Duration>,
+    /// Skip matches logged within this time since boot
+    min_uptime: Option<Duration>,
+    /// When script of event may be executed
+    active: Option<Schedule>,
+    off_schedule: OffSchedule,
+    /// All of them must be met before script is executed
+    conditions: Vec<Condition>,
+    /// The latest firing held until schedule is active again
+    deferred: Option<Deferred>,
     pub script: PathBuf,
-    pub script_timeout: Option<u64>,
+    pub script_timeout: Option<Duration>,
+    /// Signal sent to script after timeout, and how long to wait before SIGKILL
+    kill: (i32, Duration),
+    /// Wait until script is finished before launcher executes the next one
+    script_wait: bool,
+    /// Maximum number of running scripts of this event
+    max_concurrent: Option<usize>,
+    /// `SYSLOG_IDENTIFIER` of script output in the journal
+    syslog_identifier: Option<String>,
+    /// User and groups which script is executed as
+    credentials: Option<Credentials>,
 }
 
 impl Event {
+    fn new(name: String, event: settings::Event, global: Option<&Global>) -> Result<Self> {
+        let mut filters: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        for filter in event.filters.unwrap_or_default() {
+            let (key, val) = parse_filter(&filter)
+                .with_context(|| format!("Invalid filter of event `{name}`"))?;
+            filters
+                .entry(key.to_string())
+                .or_default()
+                .push(val.to_string());
+        }
+
+        // Journal field names are always uppercase but configuration keys are lowercased.
+        let mut fields: Vec<(String, Regex)> = Vec::new();
+        for (key, pattern) in event.fields.unwrap_or_default() {
+            let regex = Regex::new(&pattern).with_context(|| {
+                format!("Invalid regular expression of field `{key}` of event `{name}`")
+            })?;
+            fields.push((key.to_ascii_uppercase(), regex));
+        }
+        fields.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let message = Regex::new(&event.message)
+            .with_context(|| format!("Invalid regular expression of event `{name}`"))?;
+
+        let exclude = RegexSet::new(event.exclude.unwrap_or_default())
+            .with_context(|| format!("Invalid exclude pattern of event `{name}`"))?;
+        let compare = event
+            .compare
+            .unwrap_or_default()
+            .into_iter()
+            .map(Compare::new)
+            .collect::<Result<Vec<Compare>>>()
+            .with_context(|| format!("Invalid compare of event `{name}`"))?;
+
+        if event.threshold.as_ref().is_some_and(|t| t.count == 0) {
+            bail!("Threshold count of event `{name}` must be greater than zero");
+        }
+
+        if event.kind == EventKind::Absence && event.expect_every.is_none() {
+            bail!("Absence event `{name}` requires `expect-every`");
+        }
+
+        let sequence = match event.kind {
+            EventKind::Sequence => {
+                let steps = event
+                    .steps
+                    .unwrap_or_default()
+                    .iter()
+                    .map(|step| Regex::new(step))
+                    .collect::<Result<Vec<Regex>, regex::Error>>()
+                    .with_context(|| format!("Invalid step of sequence event `{name}`"))?;
+                if steps.is_empty() {
+                    bail!("Sequence event `{name}` requires `steps`");
+                }
+                let Some(within) = event.within else {
+                    bail!("Sequence event `{name}` requires `within`");
+                };
+                Some(Sequence {
+                    steps,
+                    within,
+                    correlate: event.correlate,
+                    pending: BTreeMap::new(),
+                })
+            }
+            _ => None,
+        };
+
+        let cancel_on = event
+            .cancel_on
+            .as_deref()
+            .map(Regex::new)
+            .transpose()
+            .with_context(|| format!("Invalid cancel pattern of event `{name}`"))?;
+        if cancel_on.is_some() && event.delay.is_none() {
+            bail!("`cancel-on` of event `{name}` requires `delay`");
+        }
+        if let Some(cancel_key) = &event.cancel_key {
+            let Some(cancel_on) = &cancel_on else {
+                bail!("`cancel-key` of event `{name}` requires `cancel-on`");
+            };
+            // Otherwise postponed executions are never canceled
+            if !has_group(&message, cancel_key) || !has_group(cancel_on, cancel_key) {
+                bail!(
+                    "`cancel-key` of event `{name}`, `{cancel_key}` must be a capture group of both `message` and `cancel-on`"
+                );
+            }
+        }
+
+        let resolve_on = event
+            .resolve_on
+            .as_deref()
+            .map(Regex::new)
+            .transpose()
+            .with_context(|| format!("Invalid resolve pattern of event `{name}`"))?;
+        if resolve_on.is_some() != event.on_resolve.is_some() {
+            bail!("`resolve-on` and `on-resolve` of event `{name}` must be set together");
+        }
+
+        if event.throttle_key.is_some() && event.next_watch_delay.is_none() {
+            bail!("`throttle-key` of event `{name}` requires `next-watch-delay`");
+        }
+        if event.throttle_mode != ThrottleMode::Leading && event.next_watch_delay.is_none() {
+            bail!("`throttle-mode` of event `{name}` requires `next-watch-delay`");
+        }
+        if let Some(backoff) = &event.backoff {
+            if event.next_watch_delay.is_none() {
+                bail!("`backoff` of event `{name}` requires `next-watch-delay`");
+            }
+            if !backoff.multiplier.is_finite() || backoff.multiplier < 1.0 {
+                bail!("Backoff multiplier of event `{name}` must be at least 1");
+            }
+            // Quiet period of trailing edge throttle is always `next-watch-delay`
+            if event.throttle_mode != ThrottleMode::Leading {
+                bail!("`backoff` of event `{name}` requires `throttle-mode = \"leading\"`");
+            }
+        }
+
+        // Per-event script timeout overrides global one
+        let script_timeout = match event.script_timeout {
+            Some(script_timeout) => script_timeout,
+            None => global.and_then(|global| global.script_timeout),
+        };
+
+        let kill_signal = event
+            .kill_signal
+            .as_deref()
+            .map(script::parse_signal)
+            .transpose()
+            .with_context(|| format!("Invalid kill signal of event `{name}`"))?;
+        let kill = (
+            kill_signal.unwrap_or(libc::SIGTERM),
+            event.kill_grace.unwrap_or(DEFAULT_KILL_GRACE),
+        );
+
+        let credentials = Credentials::new(
+            event.user.as_deref(),
+            event.group.as_deref(),
+            event.supplementary_groups.as_deref(),
+        )
+        .with_context(|| format!("Invalid user or group of event `{name}`"))?;
+        // Otherwise script fails with permission denied on every firing
+        if let Some(credentials) = &credentials {
+            for script in std::iter::once(&event.script).chain(event.on_resolve.as_ref()) {
+                credentials
+                    .check_executable(Path::new(script))
+                    .with_context(|| format!("Invalid script of event `{name}`"))?;
+            }
+        }
+
+        if event.max_concurrent == Some(0) {
+            bail!("Max concurrent of event `{name}` must be greater than zero");
+        }
+
+        if event.aggregate.is_some() && event.delay.is_some() {
+            bail!("`aggregate` of event `{name}` cannot be used with `delay`");
+        }
+        // Next watch delay would skip matches inside aggregate window
+        if event.aggregate.is_some() && event.next_watch_delay.is_some() {
+            bail!("`aggregate` of event `{name}` cannot be used with `next-watch-delay`");
+        }
+        if event.aggregate.as_ref().is_some_and(|a| a.max == 0) {
+            bail!("Aggregate max of event `{name}` must be greater than zero");
+        }
+
+        let dedup = event
+            .dedup
+            .map(Dedup::new)
+            .transpose()
+            .with_context(|| format!("Invalid dedup of event `{name}`"))?;
+        let mut conditions: Vec<Condition> = Vec::new();
+        for path in event.condition_path_exists.unwrap_or_default() {
+            conditions.push(Condition::path_exists(&path));
+        }
+        for unit in event.condition_unit_active.unwrap_or_default() {
+            conditions.push(Condition::unit_active(&unit));
+        }
+        for file_contains in event.condition_file_contains.unwrap_or_default() {
+            conditions.push(
+                Condition::file_contains(file_contains)
+                    .with_context(|| format!("Invalid condition of event `{name}`"))?,
+            );
+        }
+        let active = event
+            .active
+            .map(Schedule::new)
+            .transpose()
+            .with_context(|| format!("Invalid active schedule of event `{name}`"))?;
+
+        Ok(Event {
+            name,
+            kind: event.kind,
+            filters,
+            message,
+            fields,
+            exclude,
+            excluded: 0,
+            compare,
+            when: event.when,
+            next_watch_delay: event.next_watch_delay,
+            throttle_key: event.throttle_key,
+            last_found: BTreeMap::new(),
+            backoff: event.backoff,
+            throttle_mode: event.throttle_mode,
+            debounced: BTreeMap::new(),
+            threshold: event.threshold,
+            recent: VecDeque::new(),
+            expect_every: event.expect_every,
+            last_seen: None,
+            expected_by: None,
+            sequence,
+            delay: event.delay,
+            cancel_on,
+            cancel_key: event.cancel_key,
+            delayed: BTreeMap::new(),
+            resolve_on,
+            on_resolve: event.on_resolve.map(PathBuf::from),
+            opened: None,
+            aggregate: event.aggregate,
+            aggregated: None,
+            quota: event.max_runs.map(Quota::new),
+            suppressed: 0,
+            dedup,
+            ignore_first: event.ignore_first,
+            min_uptime: event.min_uptime,
+            active,
+            off_schedule: event.off_schedule,
+            conditions,
+            deferred: None,
+            script: PathBuf::from(event.script),
+            script_timeout,
+            kill,
+            script_wait: event.script_wait.unwrap_or(true),
+            max_concurrent: event.max_concurrent,
+            syslog_identifier: event.syslog_identifier,
+            credentials,
+        })
+    }
+
+    /// Value of `throttle_key` in captured groups or journal entry.
+    /// All matches share the same empty key if `throttle_key` is not set or not found.
+    pub fn throttle_key(&self, entry: &Entry, captures: &Captures) -> String {
+        self.throttle_key
+            .as_ref()
+            .and_then(|name| captures.get(name).or(entry.fields.get(name)))
+            .cloned()
+            .unwrap_or_default()
+    }
+
     /// Still in next watch delay?
-    pub fn in_watch_delay(&self) -> bool {
-        self.next_watch_delay.is_some()
-            && self.last_found.is_some()
-            && self.last_found.unwrap().elapsed() <= self.next_watch_delay.unwrap()
+    pub fn in_watch_delay(&self, key: &str) -> bool {
+        match self.last_found.get(key) {
+            Some(last_found) => last_found.found.elapsed() <= last_found.delay,
+            None => false,
+        }
+    }
+
+    /// Record match suppressed by next watch delay, it keeps back off from being reset.
+    pub fn record_suppressed(&mut self, key: &str) {
+        if let Some(last_found) = self.last_found.get_mut(key) {
+            last_found.matched = Instant::now();
+        }
+    }
+
+    pub fn record_last_found(&mut self, key: &str) {
+        let Some(next_watch_delay) = self.next_watch_delay else {
+            return;
+        };
+
+        // Escalate next watch delay if the event keeps matching, until it is quiet for reset period.
+        let delay = match (&self.backoff, self.last_found.get(key)) {
+            (Some(backoff), Some(LastFound { delay, matched, .. }))
+                if matched.elapsed() < backoff.reset_after =>
+            {
+                let escalated =
+                    Duration::try_from_secs_f64(delay.as_secs_f64() * backoff.multiplier)
+                        .unwrap_or(backoff.max_delay)
+                        .min(backoff.max_delay);
+                debug!(
+                    "Back off `{}`, next watch delay is {}",
+                    self.name,
+                    humantime::format_duration(Duration::from_secs(escalated.as_secs()))
+                );
+                escalated
+            }
+            _ => next_watch_delay,
+        };
+
+        let now = Instant::now();
+        self.last_found.insert(
+            key.to_string(),
+            LastFound {
+                found: now,
+                delay,
+                matched: now,
+            },
+        );
+        if self.last_found.len() <= THROTTLE_KEYS {
+            return;
+        }
+
+        // Forget keys out of next watch delay and reset period, then the least recently found key
+        let reset_after = self
+            .backoff
+            .as_ref()
+            .map(|backoff| backoff.reset_after)
+            .unwrap_or_default();
+        self.last_found.retain(|_, last_found| {
+            last_found.found.elapsed() <= last_found.delay
+                || last_found.matched.elapsed() <= reset_after
+        });
+        if self.last_found.len() > THROTTLE_KEYS {
+            if let Some(oldest) = self
+                .last_found
+                .iter()
+                .min_by_key(|(_, last_found)| last_found.matched)
+                .map(|(key, _)| key.clone())
+            {
+                self.last_found.remove(&oldest);
+            }
+        }
+    }
+
+    /// Suppress match until it is quiet for next watch delay, only the latest match is kept.
+    pub fn debounce(&mut self, key: String, captures: Captures, entry: &Entry, envs: Vec<EnvVar>) {
+        let Some(next_watch_delay) = self.next_watch_delay else {
+            return;
+        };
+
+        let count = self.debounced.get(&key).map(|d| d.count).unwrap_or(0) + 1;
+        self.debounced.insert(
+            key,
+            Debounced {
+                entry: entry.clone(),
+                captures,
+                envs,
+                count,
+                deadline: Instant::now() + next_watch_delay,
+            },
+        );
+    }
+
+    /// Take the latest suppressed matches which have been quiet until `now`
+    pub fn take_debounced(&mut self, now: Instant) -> Vec<(String, Debounced)> {
+        let due: Vec<String> = self
+            .debounced
+            .iter()
+            .filter(|(_, debounced)| debounced.deadline <= now)
+            .map(|(key, _)| key.clone())
+            .collect();
+        due.into_iter()
+            .filter_map(|key| self.debounced.remove(&key).map(|d| (key, d)))
+            .collect()
+    }
+
+    /// Record a match inside sliding window of threshold.
+    /// Return the matches inside the window when the threshold is reached.
+    pub fn record_threshold(&mut self, timestamp: SystemTime) -> Option<ThresholdWindow> {
+        let threshold = self.threshold.as_ref()?;
+
+        self.recent.push_back(timestamp);
+        while let Some(first) = self.recent.front() {
+            match timestamp.duration_since(*first) {
+                Ok(elapsed) if elapsed > threshold.within => self.recent.pop_front(),
+                _ => break,
+            };
+        }
+
+        if self.recent.len() < threshold.count {
+            return None;
+        }
+
+        let window = ThresholdWindow {
+            count: self.recent.len(),
+            first: *self.recent.front().unwrap(),
+            last: *self.recent.back().unwrap(),
+        };
+        self.recent.clear();
+        Some(window)
+    }
+
+    /// Start waiting for the next matching entry of absence event
+    pub fn arm(&mut self) {
+        if self.kind != EventKind::Absence {
+            return;
+        }
+        if let Some(expect_every) = self.expect_every {
+            self.expected_by = Some(Instant::now() + expect_every);
+        }
+    }
+
+    /// Record matching entry of absence event and wait for the next one
+    pub fn see(&mut self, entry: &Entry) {
+        if self.kind == EventKind::Absence {
+            self.last_seen = Some(entry.clone());
+            self.arm();
+        }
+    }
+
+    /// Stop waiting if no matching entry arrives until `now`.
+    /// Return true if absence event is due, then it must be armed again.
+    pub fn disarm(&mut self, now: Instant) -> bool {
+        match self.expected_by {
+            Some(deadline) if deadline <= now => {
+                self.expected_by = None;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Postpone script execution until `delay` passes.
+    /// Return false if the same problem is already postponed.
+    pub fn postpone(&mut self, entry: &Entry, captures: &Captures, envs: Vec<EnvVar>) -> bool {
+        let Some(delay) = self.delay else {
+            return false;
+        };
+
+        let key = self
+            .cancel_key
+            .as_ref()
+            .and_then(|name| captures.get(name))
+            .cloned()
+            .unwrap_or_default();
+        if self.delayed.contains_key(&key) {
+            return false;
+        }
+
+        self.delayed.insert(
+            key,
+            Delayed {
+                entry: entry.clone(),
+                envs,
+                deadline: Instant::now() + delay,
+            },
+        );
+        true
+    }
+
+    /// Drop postponed script executions resolved by recovery message.
+    /// Return the number of dropped executions.
+    pub fn cancel(&mut self, log_msg: &str) -> usize {
+        let Some(captures) = self
+            .cancel_on
+            .as_ref()
+            .and_then(|cancel_on| captures_of(cancel_on, log_msg))
+        else {
+            return 0;
+        };
+
+        let before = self.delayed.len();
+        match &self.cancel_key {
+            Some(name) => {
+                // Recovery message does not tell which one is resolved
+                let Some(key) = captures.get(name) else {
+                    return 0;
+                };
+                self.delayed.remove(key);
+            }
+            None => self.delayed.clear(),
+        }
+        before - self.delayed.len()
+    }
+
+    /// Take postponed script executions whose delay has passed until `now`
+    pub fn take_delayed(&mut self, now: Instant) -> Vec<Delayed> {
+        let due: Vec<String> = self
+            .delayed
+            .iter()
+            .filter(|(_, delayed)| delayed.deadline <= now)
+            .map(|(key, _)| key.clone())
+            .collect();
+        due.iter()
+            .filter_map(|key| self.delayed.remove(key))
+            .collect()
+    }
+
+    /// Collect match into aggregate window. The first match opens the window.
+    pub fn collect(&mut self, entry: &Entry, envs: Vec<EnvVar>) {
+        let Some(aggregate) = &self.aggregate else {
+            return;
+        };
+
+        let aggregated = self.aggregated.get_or_insert_with(|| Aggregated {
+            entries: Vec::new(),
+            envs,
+            count: 0,
+            deadline: Instant::now() + aggregate.window,
+        });
+        aggregated.count += 1;
+        if aggregated.entries.len() < aggregate.max {
+            aggregated.entries.push(entry.clone());
+        }
+    }
+
+    /// Take matches collected inside aggregate window, if the window is closed until `now`.
+    pub fn take_aggregated(&mut self, now: Instant) -> Option<Aggregated> {
+        match &self.aggregated {
+            Some(aggregated) if aggregated.deadline <= now => self.aggregated.take(),
+            _ => None,
+        }
+    }
+
+    /// Keep fired event open until it is resolved. The first triggering entry is kept.
+    pub fn open(&mut self, entry: &Entry) {
+        if self.on_resolve.is_some() && self.opened.is_none() {
+            self.opened = Some((entry.clone(), Instant::now()));
+        }
+    }
+
+    /// Close open event if recovery message is found.
+    /// Return triggering entry and when the event is opened.
+    pub fn close(&mut self, log_msg: &str) -> Option<(Entry, Instant)> {
+        if self.opened.is_some()
+            && self
+                .resolve_on
+                .as_ref()
+                .is_some_and(|resolve_on| resolve_on.is_match(log_msg))
+        {
+            return self.opened.take();
+        }
+        None
+    }
+
+    /// Is entry found too soon after daemon `started` or logged too soon after boot?
+    pub fn is_too_early(&self, entry: &Entry, started: Instant) -> bool {
+        let booting = match (self.min_uptime, entry.uptime) {
+            (Some(min_uptime), Some(uptime)) => uptime < min_uptime,
+            _ => false,
+        };
+        self.is_ignored(started) || booting
+    }
+
+    /// Is it too soon after daemon `started` or boot to fire absence event?
+    pub fn in_grace_period(&self, started: Instant) -> bool {
+        let booting = match (self.min_uptime, uptime()) {
+            (Some(min_uptime), Some(uptime)) => uptime < min_uptime,
+            _ => false,
+        };
+        self.is_ignored(started) || booting
+    }
+
+    /// Is it still within `ignore-first` since daemon `started`?
+    fn is_ignored(&self, started: Instant) -> bool {
+        self.ignore_first
+            .is_some_and(|ignore_first| started.elapsed() < ignore_first)
+    }
+
+    /// The first condition which is not met. Condition that cannot be checked is not met.
+    pub fn unmet_condition(&self) -> Option<&Condition> {
+        self.conditions
+            .iter()
+            .find(|condition| match condition.is_met() {
+                Ok(met) => !met,
+                Err(err) => {
+                    warn!("{err:#}");
+                    true
+                }
+            })
+    }
+
+    /// Hold firing until schedule is active again. Only the latest one is kept.
+    pub fn defer(&mut self, entry: &Entry, envs: Vec<EnvVar>, input: Option<String>) {
+        let count = self.deferred.as_ref().map_or(0, |deferred| deferred.count);
+        self.deferred = Some(Deferred {
+            entry: entry.clone(),
+            envs,
+            input,
+            count: count + 1,
+            deadline: next_minute(),
+        });
+    }
+
+    /// Take deferred firing if it is due to check schedule again until `now`
+    pub fn take_deferred(&mut self, now: Instant) -> Option<Deferred> {
+        match &self.deferred {
+            Some(deferred) if deferred.deadline <= now => self.deferred.take(),
+            _ => None,
+        }
+    }
+
+    /// When is this event due without new journal entry?
+    pub fn deadline(&self) -> Option<Instant> {
+        let absent = self.expected_by;
+        let delayed = self.delayed.values().map(|delayed| delayed.deadline).min();
+        let debounced = self.debounced.values().map(|d| d.deadline).min();
+        let aggregated = self.aggregated.as_ref().map(|a| a.deadline);
+        let deferred = self.deferred.as_ref().map(|d| d.deadline);
+        absent
+            .into_iter()
+            .chain(delayed)
+            .chain(debounced)
+            .chain(aggregated)
+            .chain(deferred)
+            .min()
+    }
+
+    /// Does journal entry pass all field filters and `when` expression of this event?
+    pub fn accepts(&self, entry: &BTreeMap<String, String>) -> bool {
+        self.filters_match(entry) && self.when.as_ref().is_none_or(|when| when.eval(entry))
+    }
+
+    /// Does journal entry pass all field filters of this event?
+    pub fn filters_match(&self, entry: &BTreeMap<String, String>) -> bool {
+        self.filters.iter().all(|(key, values)| {
+            entry
+                .get(key)
+                .map(|value| values.contains(value))
+                .unwrap_or(false)
+        })
+    }
+
+    /// Do all field regular expressions of this event match journal entry?
+    pub fn fields_match(&self, entry: &BTreeMap<String, String>) -> bool {
+        self.fields.iter().all(|(key, regex)| {
+            entry
+                .get(key)
+                .map(|value| regex.is_match(value))
+                .unwrap_or(false)
+        })
     }
 
-    pub fn record_last_found(&mut self) {
-        if self.next_watch_delay.is_some() {
-            self.last_found = Some(Instant::now());
+    /// Do all numeric comparisons hold for captured groups or journal fields?
+    /// Missing or non-numeric value is an error.
+    pub fn compares(&self, entry: &BTreeMap<String, String>, captures: &Captures) -> Result<bool> {
+        for compare in &self.compare {
+            let Some(value) = captures.get(&compare.name).or(entry.get(&compare.name)) else {
+                bail!("`{}` is not found", compare.name);
+            };
+            let number: f64 = value
+                .trim()
+                .parse()
+                .with_context(|| format!("`{}` is not a number, `{value}`", compare.name))?;
+            if !compare.holds(number) {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Does log message match one of exclude patterns?
+    pub fn is_excluded(&self, log_msg: &str) -> bool {
+        self.exclude.is_match(log_msg)
+    }
+
+    /// Extract captured groups of message regular expression.
+    pub fn captures(&self, log_msg: &str) -> Captures {
+        captures_of(&self.message, log_msg).unwrap_or_default()
+    }
+}
+
+/// Numeric comparison on value of capture group or journal field
+struct Compare {
+    name: String,
+    op: CompareOp,
+    /// Operand, or the lower bound of `between`
+    value: f64,
+    /// The upper bound of `between`
+    max: f64,
+}
+
+impl Compare {
+    pub fn new(compare: settings::Compare) -> Result<Self> {
+        let (value, max) = match (compare.op, compare.value) {
+            (CompareOp::Between, CompareValue::Range(range)) => match range[..] {
+                [min, max] if min <= max => (min, max),
+                _ => bail!(
+                    "Value of `between` for `{}` must be [min, max]",
+                    compare.name
+                ),
+            },
+            (CompareOp::Between, CompareValue::Number(_)) => {
+                bail!(
+                    "Value of `between` for `{}` must be [min, max]",
+                    compare.name
+                )
+            }
+            (_, CompareValue::Number(value)) => (value, value),
+            (_, CompareValue::Range(_)) => {
+                bail!("Value for `{}` must be a number", compare.name)
+            }
+        };
+
+        Ok(Self {
+            name: compare.name,
+            op: compare.op,
+            value,
+            max,
+        })
+    }
+
+    pub fn holds(&self, number: f64) -> bool {
+        match self.op {
+            CompareOp::Greater => number > self.value,
+            CompareOp::GreaterOrEqual => number >= self.value,
+            CompareOp::Less => number < self.value,
+            CompareOp::LessOrEqual => number <= self.value,
+            CompareOp::Between => self.value <= number && number <= self.max,
+        }
+    }
+}
+
+/// Does regular expression have capture group of name or number?
+fn has_group(regex: &Regex, name: &str) -> bool {
+    match name.parse::<usize>() {
+        Ok(index) => index > 0 && index < regex.captures_len(),
+        Err(_) => regex.capture_names().any(|group| group == Some(name)),
+    }
+}
+
+/// Extract captured groups of regular expression, `None` if it does not match.
+/// Named groups are keyed by their names, all groups are keyed by their numbers.
+fn captures_of(regex: &Regex, text: &str) -> Option<Captures> {
+    let captures = regex.captures(text)?;
+
+    let mut captured: Captures = Captures::new();
+    for (index, name) in regex.capture_names().enumerate().skip(1) {
+        let Some(value) = captures.get(index) else {
+            continue;
+        };
+        captured.insert(index.to_string(), value.as_str().to_string());
+        if let Some(name) = name {
+            captured.insert(name.to_string(), value.as_str().to_string());
         }
     }
+
+    Some(captured)
+}
+
+/// Ordered steps of sequence event
+struct Sequence {
+    steps: Vec<Regex>,
+    within: Duration,
+    /// Name of capture group or journal field that entries of the same sequence share
+    correlate: Option<String>,
+    /// Partially completed sequences keyed by correlation value
+    pending: BTreeMap<String, Vec<(Entry, Captures)>>,
+}
+
+impl Sequence {
+    /// Advance sequence with journal entry.
+    /// Return participating entries and their captured groups when the sequence completes.
+    pub fn advance(&mut self, entry: &Entry) -> Option<Vec<(Entry, Captures)>> {
+        let within = self.within;
+        self.pending.retain(|_, done| {
+            done.first().is_some_and(|(first, _)| {
+                entry
+                    .realtime
+                    .duration_since(first.realtime)
+                    .map(|elapsed| elapsed <= within)
+                    .unwrap_or(true)
+            })
+        });
+
+        // Try the furthest step first, so the same pattern can be used in more than one step.
+        for step_index in (0..self.steps.len()).rev() {
+            let Some(captures) = captures_of(&self.steps[step_index], entry.message()) else {
+                continue;
+            };
+            let Some(key) = self.key(entry, &captures) else {
+                continue;
+            };
+
+            let done = self.pending.get(&key).map(Vec::len).unwrap_or(0);
+            if step_index == 0 {
+                // Start a new sequence, or restart an incomplete one
+                self.pending
+                    .insert(key.clone(), vec![(entry.clone(), captures)]);
+            } else if done == step_index {
+                self.pending
+                    .get_mut(&key)
+                    .unwrap()
+                    .push((entry.clone(), captures));
+            } else {
+                continue;
+            }
+
+            if self.pending[&key].len() == self.steps.len() {
+                return self.pending.remove(&key);
+            }
+            return None;
+        }
+
+        None
+    }
+
+    /// Correlation value of entry, `None` if entry does not have one.
+    fn key(&self, entry: &Entry, captures: &Captures) -> Option<String> {
+        match &self.correlate {
+            Some(name) => captures.get(name).or(entry.fields.get(name)).cloned(),
+            None => Some(String::new()),
+        }
+    }
+}
+
+/// Next watch delay of a throttle key
+struct LastFound {
+    /// When script is executed, the start of next watch delay
+    found: Instant,
+    delay: Duration,
+    /// When the latest match is found, including ones suppressed by next watch delay
+    matched: Instant,
+}
+
+/// The latest match of burst suppressed by trailing edge throttle
+struct Debounced {
+    entry: Entry,
+    captures: Captures,
+    envs: Vec<EnvVar>,
+    /// Number of matches in the burst
+    count: u64,
+    deadline: Instant,
+}
+
+/// Suppress log messages with the same fingerprint within window
+struct Dedup {
+    window: Duration,
+    /// Regular expressions and their replacements to normalize log message into fingerprint
+    replacements: Vec<(Regex, String)>,
+}
+
+impl Dedup {
+    pub fn new(dedup: settings::Dedup) -> Result<Self> {
+        let mut replacements: Vec<(Regex, String)> = Vec::new();
+        for replace in dedup.replace {
+            let regex = Regex::new(&replace.pattern)
+                .with_context(|| format!("Invalid replace pattern `{}`", replace.pattern))?;
+            replacements.push((regex, replace.with));
+        }
+
+        // UUIDs contain hex and digits, hex contains digits.
+        for normalize in [Normalize::Uuid, Normalize::Hex, Normalize::Digits] {
+            if !dedup.normalize.contains(&normalize) {
+                continue;
+            }
+            let (pattern, replacement) = match normalize {
+                Normalize::Uuid => (
+                    r"[0-9a-fA-F]{8}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{12}",
+                    "<uuid>",
+                ),
+                Normalize::Hex => (r"\b0[xX][0-9a-fA-F]+\b|\b[0-9a-fA-F]{8,}\b", "<hex>"),
+                Normalize::Digits => (r"[0-9]+", "<n>"),
+            };
+            replacements.push((Regex::new(pattern)?, replacement.to_string()));
+        }
+
+        Ok(Self {
+            window: dedup.window,
+            replacements,
+        })
+    }
+
+    /// Normalize log message into fingerprint
+    pub fn fingerprint(&self, log_msg: &str) -> String {
+        self.replacements
+            .iter()
+            .fold(log_msg.to_string(), |fingerprint, (regex, replacement)| {
+                regex
+                    .replace_all(&fingerprint, replacement.as_str())
+                    .into_owned()
+            })
+    }
+}
+
+/// Days of week and time ranges in local time
+struct Schedule {
+    /// Every day if empty
+    days: Vec<chrono::Weekday>,
+    /// Start and end of time ranges. All day if empty.
+    hours: Vec<(NaiveTime, NaiveTime)>,
+}
+
+impl Schedule {
+    pub fn new(schedule: settings::Schedule) -> Result<Self> {
+        let days = schedule
+            .days
+            .into_iter()
+            .map(|day| match day {
+                settings::Weekday::Mon => chrono::Weekday::Mon,
+                settings::Weekday::Tue => chrono::Weekday::Tue,
+                settings::Weekday::Wed => chrono::Weekday::Wed,
+                settings::Weekday::Thu => chrono::Weekday::Thu,
+                settings::Weekday::Fri => chrono::Weekday::Fri,
+                settings::Weekday::Sat => chrono::Weekday::Sat,
+                settings::Weekday::Sun => chrono::Weekday::Sun,
+            })
+            .collect();
+
+        let mut hours = Vec::new();
+        for range in &schedule.hours {
+            let Some((start, end)) = range.split_once('-') else {
+                bail!("Incorrect time range format, {range}");
+            };
+            let start = NaiveTime::parse_from_str(start.trim(), "%H:%M")
+                .with_context(|| format!("Invalid start time of range `{range}`"))?;
+            let end = NaiveTime::parse_from_str(end.trim(), "%H:%M")
+                .with_context(|| format!("Invalid end time of range `{range}`"))?;
+            hours.push((start, end));
+        }
+
+        Ok(Self { days, hours })
+    }
+
+    /// Is local time inside schedule?
+    /// A time range whose end is not after its start continues to the next day.
+    pub fn contains(&self, time: NaiveDateTime) -> bool {
+        let on_day = |day: chrono::Weekday| self.days.is_empty() || self.days.contains(&day);
+        if self.hours.is_empty() {
+            return on_day(time.weekday());
+        }
+
+        let now = time.time();
+        self.hours.iter().any(|(start, end)| {
+            if start < end {
+                on_day(time.weekday()) && *start <= now && now < *end
+            } else {
+                (on_day(time.weekday()) && *start <= now)
+                    || (on_day(time.weekday().pred()) && now < *end)
+            }
+        })
+    }
+}
+
+/// Firing held outside schedule
+struct Deferred {
+    entry: Entry,
+    envs: Vec<EnvVar>,
+    input: Option<String>,
+    /// Number of firings held since schedule is inactive
+    count: u64,
+    /// When to check schedule again
+    deadline: Instant,
+}
+
+/// The beginning of the next minute, the resolution of schedule
+fn next_minute() -> Instant {
+    Instant::now() + Duration::from_secs(60 - u64::from(Local::now().second()))
+}
+
+/// Sliding window of script runs limited by `max-runs`
+struct Quota {
+    max_runs: MaxRuns,
+    runs: VecDeque<Instant>,
+}
+
+impl Quota {
+    pub fn new(max_runs: MaxRuns) -> Self {
+        Self {
+            max_runs,
+            runs: VecDeque::new(),
+        }
+    }
+
+    /// Can script run at `now` without exceeding max runs?
+    pub fn available(&mut self, now: Instant) -> bool {
+        while let Some(first) = self.runs.front() {
+            if now.saturating_duration_since(*first) < self.max_runs.per {
+                break;
+            }
+            self.runs.pop_front();
+        }
+        self.runs.len() < self.max_runs.count
+    }
+
+    pub fn record(&mut self, now: Instant) {
+        self.runs.push_back(now);
+    }
+}
+
+/// Matches collected inside aggregate window
+struct Aggregated {
+    /// Entries up to aggregate max
+    entries: Vec<Entry>,
+    /// Env vars of the first match
+    envs: Vec<EnvVar>,
+    /// Number of all matches, including ones beyond aggregate max
+    count: u64,
+    deadline: Instant,
+}
+
+/// Script execution postponed until delay passes without recovery message
+struct Delayed {
+    entry: Entry,
+    envs: Vec<EnvVar>,
+    deadline: Instant,
+}
+
+/// Matches inside sliding window when threshold is reached
+#[derive(Debug, PartialEq, Eq)]
+struct ThresholdWindow {
+    count: usize,
+    first: SystemTime,
+    last: SystemTime,
+}
+
+impl ThresholdWindow {
+    pub fn envs(&self) -> Vec<EnvVar> {
+        vec![
+            EnvVar::Custom {
+                key: "THRESHOLD_COUNT".to_string(),
+                value: self.count.to_string(),
+            },
+            EnvVar::Custom {
+                key: "THRESHOLD_FIRST".to_string(),
+                value: realtime_usec(self.first).to_string(),
+            },
+            EnvVar::Custom {
+                key: "THRESHOLD_LAST".to_string(),
+                value: realtime_usec(self.last).to_string(),
+            },
+        ]
+    }
+}
+
+/// Matched event and captured groups of its message regular expression
+struct Found {
+    event_index: usize,
+    captures: Captures,
+}
+
+/// Split journal field filter, `KEY=VALUE` into key and value.
+fn parse_filter(filter: &str) -> Result<(&str, &str)> {
+    match filter.split_once('=') {
+        Some((key, val)) if !key.is_empty() => Ok((key, val)),
+        _ => bail!("Incorrect filter format, {filter}"),
+    }
 }
 
 pub struct Monitor {
     filters: Option<Vec<String>>,
     events: Vec<Event>,
+    event_regex_set: RegexSet,
+    /// Script runs of all events limited by global `max-runs`
+    quota: Option<Quota>,
+    /// When fingerprint of log message is found, keyed by event index and fingerprint
+    fingerprints: BTreeMap<(usize, String), Instant>,
+    /// When scripts of all events are not executed
+    quiet_hours: Option<Schedule>,
+    /// When monitor is created, for `ignore-first` of events
+    started: Instant,
     launcher: Launcher,
 }
 
@@ -56,22 +1201,93 @@ impl Monitor {
             .events
             .unwrap()
             .into_iter()
-            .map(|(name, event)| Event {
-                name,
-                msg_filter: event.message,
-                next_watch_delay: event.next_watch_delay,
-                last_found: None,
-                script: PathBuf::from(event.script),
-                script_timeout: settings.global.as_ref().unwrap().script_timeout,
-            })
-            .collect();
+            .map(|(name, event)| Event::new(name, event, settings.global.as_ref()))
+            .collect::<Result<Vec<Event>>>()?;
+        let event_regex_set = RegexSet::new(events.iter().map(|event| event.message.as_str()))
+            .context("Invalid regular expressions for matching log message")?;
+        let (filters, max_runs, quiet_hours, max_concurrent) = match settings.global {
+            Some(global) => (
+                global.filters,
+                global.max_runs,
+                global.quiet_hours,
+                global.max_concurrent,
+            ),
+            None => (None, None, None, None),
+        };
+        let max_concurrent = max_concurrent.unwrap_or(DEFAULT_MAX_CONCURRENT);
+        if max_concurrent == 0 {
+            bail!("Global max concurrent must be greater than zero");
+        }
+        let quiet_hours = quiet_hours
+            .map(Schedule::new)
+            .transpose()
+            .context("Invalid quiet hours")?;
         Ok(Self {
-            filters: settings.global.and_then(|v| v.filters),
+            filters,
             events,
-            launcher: Launcher::new()?,
+            event_regex_set,
+            quota: max_runs.map(Quota::new),
+            fingerprints: BTreeMap::new(),
+            quiet_hours,
+            started: Instant::now(),
+            launcher: Launcher::new(max_concurrent)?,
         })
     }
 
+    /// May script of event be executed at local time?
+    fn is_active(&self, event_index: usize, time: NaiveDateTime) -> bool {
+        let active = self.events[event_index]
+            .active
+            .as_ref()
+            .is_none_or(|active| active.contains(time));
+        let quiet = self
+            .quiet_hours
+            .as_ref()
+            .is_some_and(|quiet_hours| quiet_hours.contains(time));
+        active && !quiet
+    }
+
+    /// Is fingerprint of log message already found within dedup window of event?
+    /// Otherwise, the fingerprint is recorded.
+    fn is_duplicate(&mut self, event_index: usize, log_msg: &str) -> bool {
+        let Some(dedup) = &self.events[event_index].dedup else {
+            return false;
+        };
+
+        let now = Instant::now();
+        let window = dedup.window;
+        let key = (event_index, dedup.fingerprint(log_msg));
+        if let Some(found) = self.fingerprints.get(&key) {
+            if now.saturating_duration_since(*found) <= window {
+                return true;
+            }
+        }
+
+        self.fingerprints.insert(key, now);
+        if self.fingerprints.len() > FINGERPRINTS {
+            // Forget expired fingerprints, then the oldest one
+            let events = &self.events;
+            self.fingerprints.retain(|(event_index, _), found| {
+                events[*event_index]
+                    .dedup
+                    .as_ref()
+                    .is_some_and(|dedup| now.saturating_duration_since(*found) <= dedup.window)
+            });
+            if self.fingerprints.len() > FINGERPRINTS {
+                if let Some(oldest) = self
+                    .fingerprints
+                    .iter()
+                    .min_by_key(|(_, found)| **found)
+                    .map(|(key, _)| key.clone())
+                {
+                    self.fingerprints.remove(&oldest);
+                }
+            }
+        }
+
+        false
+    }
+
     pub fn watch(&mut self) -> Result<()> {
         // Open all kind (system + kernel + user) of log journal for reading.
         let mut journal: Journal = journal::OpenOptions::default()
@@ -81,135 +1297,587 @@ impl Monitor {
             .open()
             .context("Failed to open the log journal (system + kernel + user) for reading")?;
 
-        // Add filters
-        if let Some(filters) = &self.filters {
-            for filter in filters {
-                debug!("Add filter: {filter}");
-                let (key, val) = {
-                    let field = filter.split('=').collect::<Vec<&str>>();
-                    if field.len() != 2 {
-                        warn!("Incorrect filter format, {filter}");
-                        continue;
-                    }
-                    (field[0], field[1])
-                };
-                journal
-                    .match_add(key, val)
-                    .with_context(|| format!("Could not add journal filter `{key}={val}`"))?;
+        // Add filters
+        if let Some(filters) = &self.filters {
+            for filter in filters {
+                debug!("Add filter: {filter}");
+                let (key, val) = match parse_filter(filter) {
+                    Ok(field) => field,
+                    Err(err) => {
+                        warn!("{err:#}");
+                        continue;
+                    }
+                };
+                journal
+                    .match_add(key, val)
+                    .with_context(|| format!("Could not add journal filter `{key}={val}`"))?;
+            }
+        }
+
+        debug!("Notify systemd that we are ready :)");
+        if !daemon::notify(false, [("READY", "1")].iter())
+            .context("Could not notify systemd, READY=1")?
+        {
+            error!("Cannot notify systemd, READY=1");
+        }
+
+        let notify_msg = "Start monitor journal message...";
+        if !daemon::notify(false, [("STATUS", &notify_msg)].iter())
+            .context("Could notify systemd, STATUS={notify_msg}")?
+        {
+            error!("Cannot notify systemd, STATUS={notify_msg}");
+        }
+
+        info!("{notify_msg}");
+
+        // Absence events fire even if no matching message is ever found
+        for event in &mut self.events {
+            event.arm();
+        }
+
+        // Go to end of journal before start waiting for new entry
+        journal
+            .seek_tail()
+            .context("Failed to move to the position after the most recent available entry")?;
+        if journal
+            .previous()
+            .context("Could not move to previous journal entry")?
+            != 1
+        {
+            bail!("Cannot move to the most recent journal entry");
+        }
+
+        'watch_new_entry: loop {
+            self.expire();
+
+            // Wait for new journal entry or the earliest deadline of events
+            let fields = match journal
+                .next_entry()
+                .context("Failed to read the next entry from the journal")?
+            {
+                Some(new_entry) => new_entry,
+                None => 'until_new_entry: loop {
+                    let wait_time = self
+                        .next_deadline()
+                        .map(|deadline| deadline.saturating_duration_since(Instant::now()));
+                    if let Some(new_entry) = journal
+                        .await_next_entry(wait_time)
+                        .context("Failed to read the next entry from the journal")?
+                    {
+                        break 'until_new_entry new_entry;
+                    }
+                    self.expire();
+                },
+            };
+
+            if !fields.contains_key("MESSAGE") {
+                continue 'watch_new_entry;
+            }
+            let entry = Entry {
+                fields,
+                realtime: journal
+                    .timestamp()
+                    .context("Failed to get realtime timestamp of journal entry")?,
+                uptime: journal
+                    .monotonic_timestamp_current_boot()
+                    .ok()
+                    .map(Duration::from_micros),
+            };
+            let log_msg = entry.message();
+            debug!("MESSAGE: {log_msg}");
+
+            self.recover(&entry);
+
+            for found in self
+                .matches(log_msg, &entry.fields)
+                .with_context(|| format!("Could not match log message `{log_msg}`"))?
+            {
+                let event_index = found.event_index;
+                if let Err(err) = self.respond(found, &entry).with_context(|| {
+                    format!("Failed to respond to `{}", self.events[event_index].name)
+                }) {
+                    warn!("{err:#}");
+                }
+            }
+        }
+    }
+
+    fn respond(&mut self, mut found: Found, entry: &Entry) -> Result<()> {
+        let event_index = found.event_index;
+        let mut envs: Vec<EnvVar> = Vec::new();
+
+        // Matching message of absence event is always expected, even too soon after start or boot.
+        if self.events[event_index].kind == EventKind::Absence {
+            self.events[event_index].see(entry);
+            debug!(
+                "Re-arm `{}`, expect next message within {}",
+                self.events[event_index].name,
+                humantime::format_duration(self.events[event_index].expect_every.unwrap())
+            );
+            return Ok(());
+        }
+
+        if self.events[event_index].is_too_early(entry, self.started) {
+            debug!(
+                "Skip `{}`, it is found too soon after start or boot.",
+                self.events[event_index].name
+            );
+            return Ok(());
+        }
+
+        match self.events[event_index].kind {
+            EventKind::Match | EventKind::Absence => {}
+            EventKind::Sequence => {
+                let Some(steps) = self.events[event_index]
+                    .sequence
+                    .as_mut()
+                    .and_then(|sequence| sequence.advance(entry))
+                else {
+                    return Ok(());
+                };
+
+                // Captured groups of later steps take precedence
+                for (_, captures) in &steps {
+                    found.captures.extend(captures.clone());
+                }
+
+                let sequence = steps
+                    .iter()
+                    .map(|(step, _)| &step.fields)
+                    .collect::<Vec<&BTreeMap<String, String>>>();
+                envs.push(EnvVar::Custom {
+                    key: "SEQUENCE".to_string(),
+                    value: serde_json::to_string(&sequence).with_context(|| {
+                        format!("Failed to serialize `{sequence:?}` to string of JSON")
+                    })?,
+                });
+            }
+        }
+
+        if self.is_duplicate(event_index, entry.message()) {
+            debug!(
+                "Skip `{}`, the same fingerprint is found within dedup window.",
+                self.events[event_index].name
+            );
+            return Ok(());
+        }
+
+        let throttle_key = self.events[event_index].throttle_key(entry, &found.captures);
+        let debounce = match self.events[event_index].throttle_mode {
+            ThrottleMode::Leading => false,
+            ThrottleMode::Trailing => true,
+            ThrottleMode::Both => self.events[event_index].in_watch_delay(&throttle_key),
+        };
+        if debounce {
+            self.events[event_index].record_suppressed(&throttle_key);
+            self.events[event_index].debounce(throttle_key, found.captures, entry, envs);
+            debug!(
+                "Debounce `{}`, wait until it is quiet for next watch delay.",
+                self.events[event_index].name
+            );
+            return Ok(());
+        }
+
+        if self.events[event_index].in_watch_delay(&throttle_key) {
+            self.events[event_index].record_suppressed(&throttle_key);
+            debug!(
+                "Skip `{}`, it is still in next watch delay.",
+                self.events[event_index].name
+            );
+            return Ok(());
+        }
+
+        self.proceed(event_index, &throttle_key, found.captures, entry, envs)
+    }
+
+    /// Respond to match that passes next watch delay
+    fn proceed(
+        &mut self,
+        event_index: usize,
+        throttle_key: &str,
+        captures: Captures,
+        entry: &Entry,
+        mut envs: Vec<EnvVar>,
+    ) -> Result<()> {
+        if self.events[event_index].threshold.is_some() {
+            let Some(window) = self.events[event_index].record_threshold(entry.realtime) else {
+                debug!(
+                    "Skip `{}`, it has not reached threshold yet.",
+                    self.events[event_index].name
+                );
+                return Ok(());
+            };
+            envs.extend(window.envs());
+        }
+
+        self.events[event_index].record_last_found(throttle_key);
+
+        envs.extend(captures.iter().map(|(name, value)| EnvVar::Capture {
+            name: name.clone(),
+            value: value.clone(),
+        }));
+
+        if self.events[event_index].aggregate.is_some() {
+            self.events[event_index].collect(entry, envs);
+            debug!(
+                "Collect `{}` until aggregate window is closed.",
+                self.events[event_index].name
+            );
+            return Ok(());
+        }
+
+        if let Some(delay) = self.events[event_index].delay {
+            if self.events[event_index].postpone(entry, &captures, envs) {
+                debug!(
+                    "Postpone `{}` for {}",
+                    self.events[event_index].name,
+                    humantime::format_duration(delay)
+                );
+            } else {
+                debug!(
+                    "Skip `{}`, it is already postponed.",
+                    self.events[event_index].name
+                );
+            }
+            return Ok(());
+        }
+
+        self.fire(event_index, entry, envs, None)
+    }
+
+    /// Drop postponed script executions and resolve open events by recovery message.
+    /// Recovery message must pass field filters and field regular expressions of the event.
+    fn recover(&mut self, entry: &Entry) {
+        let log_msg = entry.message();
+        for event_index in 0..self.events.len() {
+            if self.events[event_index].cancel_on.is_none()
+                && self.events[event_index].resolve_on.is_none()
+            {
+                continue;
+            }
+            if !self.events[event_index].filters_match(&entry.fields)
+                || !self.events[event_index].fields_match(&entry.fields)
+            {
+                continue;
+            }
+
+            let canceled = self.events[event_index].cancel(log_msg);
+            if canceled > 0 {
+                info!(
+                    "Cancel `{}`, recovery message is found ({canceled} postponed)",
+                    self.events[event_index].name
+                );
+            }
+
+            if let Err(err) = self
+                .resolve(event_index, entry)
+                .with_context(|| format!("Failed to resolve `{}`", self.events[event_index].name))
+            {
+                warn!("{err:#}");
+            }
+        }
+    }
+
+    /// Execute resolve script of open event
+    fn resolve(&mut self, event_index: usize, entry: &Entry) -> Result<()> {
+        let Some((trigger, opened)) = self.events[event_index].close(entry.message()) else {
+            return Ok(());
+        };
+        let on_resolve = self.events[event_index].on_resolve.clone().unwrap();
+        let duration = opened.elapsed();
+
+        info!(
+            "Resolve EVENT: `{name}` after {duration}, LOG_MESSAGE: `{log_msg}` => Try to execute `{script}`",
+            name = self.events[event_index].name,
+            duration = humantime::format_duration(Duration::from_secs(duration.as_secs())),
+            log_msg = entry.message(),
+            script = on_resolve.display()
+        );
+
+        let envs = vec![
+            EnvVar::Custom {
+                key: "TRIGGER_MESSAGE".to_string(),
+                value: trigger.message().to_string(),
+            },
+            EnvVar::Custom {
+                key: "TRIGGER_JSON".to_string(),
+                value: serde_json::to_string(&trigger.fields).with_context(|| {
+                    format!(
+                        "Failed to serialize `{:?}` to string of JSON",
+                        trigger.fields
+                    )
+                })?,
+            },
+            EnvVar::Custom {
+                key: "OPEN_DURATION".to_string(),
+                value: duration.as_secs().to_string(),
+            },
+        ];
+        self.launch(event_index, &on_resolve, entry, envs, None)
+    }
+
+    /// The earliest deadline of all events
+    fn next_deadline(&self) -> Option<Instant> {
+        self.events.iter().filter_map(Event::deadline).min()
+    }
+
+    /// Fire events whose deadline has passed
+    fn expire(&mut self) {
+        let now = Instant::now();
+        for event_index in 0..self.events.len() {
+            if self.events[event_index]
+                .deadline()
+                .is_some_and(|deadline| deadline <= now)
+            {
+                if let Err(err) = self.on_deadline(event_index, now).with_context(|| {
+                    format!("Failed to respond to `{}", self.events[event_index].name)
+                }) {
+                    warn!("{err:#}");
+                }
             }
         }
+    }
 
-        debug!("Notify systemd that we are ready :)");
-        if !daemon::notify(false, [("READY", "1")].iter())
-            .context("Could not notify systemd, READY=1")?
-        {
-            error!("Cannot notify systemd, READY=1");
+    fn on_deadline(&mut self, event_index: usize, now: Instant) -> Result<()> {
+        // Aggregate window is closed
+        if let Some(aggregated) = self.events[event_index].take_aggregated(now) {
+            let mut envs = aggregated.envs;
+            envs.push(EnvVar::Custom {
+                key: "AGGREGATE_COUNT".to_string(),
+                value: aggregated.count.to_string(),
+            });
+            let input = serde_json::json!({
+                "count": aggregated.count,
+                "entries": aggregated.entries.iter().map(|entry| &entry.fields).collect::<Vec<_>>(),
+            })
+            .to_string();
+            self.fire(event_index, &aggregated.entries[0], envs, Some(input))?;
         }
 
-        let notify_msg = "Start monitor journal message...";
-        if !daemon::notify(false, [("STATUS", &notify_msg)].iter())
-            .context("Could notify systemd, STATUS={notify_msg}")?
-        {
-            error!("Cannot notify systemd, STATUS={notify_msg}");
+        // Burst of matches has been quiet for next watch delay
+        for (throttle_key, debounced) in self.events[event_index].take_debounced(now) {
+            let mut envs = debounced.envs;
+            envs.push(EnvVar::Custom {
+                key: "THROTTLED".to_string(),
+                value: (debounced.count - 1).to_string(),
+            });
+            self.proceed(
+                event_index,
+                &throttle_key,
+                debounced.captures,
+                &debounced.entry,
+                envs,
+            )?;
         }
 
-        info!("{notify_msg}");
+        // No recovery message has arrived within delay
+        for delayed in self.events[event_index].take_delayed(now) {
+            self.fire(event_index, &delayed.entry, delayed.envs, None)?;
+        }
 
-        // Go to end of journal before start waiting for new entry
-        journal
-            .seek_tail()
-            .context("Failed to move to the position after the most recent available entry")?;
-        if journal
-            .previous()
-            .context("Could not move to previous journal entry")?
-            != 1
-        {
-            bail!("Cannot move to the most recent journal entry");
+        // Check schedule of held firing again
+        if let Some(mut deferred) = self.events[event_index].take_deferred(now) {
+            if self.is_active(event_index, Local::now().naive_local()) {
+                let mut envs = deferred.envs;
+                envs.push(EnvVar::Custom {
+                    key: "DEFERRED".to_string(),
+                    value: deferred.count.to_string(),
+                });
+                self.fire(event_index, &deferred.entry, envs, deferred.input)?;
+            } else {
+                deferred.deadline = next_minute();
+                self.events[event_index].deferred = Some(deferred);
+            }
         }
 
-        'watch_new_entry: loop {
-            // Wait for new journal entry
-            let entry = match journal
-                .next_entry()
-                .context("Failed to read the next entry from the journal")?
-            {
-                Some(new_entry) => new_entry,
-                None => 'until_new_entry: loop {
-                    if let Some(new_entry) = journal
-                        .await_next_entry(None)
-                        .context("Failed to read the next entry from the journal")?
-                    {
-                        break 'until_new_entry new_entry;
-                    }
-                },
-            };
+        // No matching message has arrived within expected interval
+        if self.events[event_index].disarm(now) {
+            info!(
+                "No message of `{}` within {}",
+                self.events[event_index].name,
+                humantime::format_duration(self.events[event_index].expect_every.unwrap())
+            );
 
-            let Some(log_msg) = entry.get("MESSAGE") else {
-                continue 'watch_new_entry;
-            };
-            debug!("MESSAGE: {log_msg}");
+            // Keep firing every interval while matching message is missing
+            self.events[event_index].arm();
 
-            for event_index in self
-                .matches(log_msg)
-                .with_context(|| format!("Could not match log message `{log_msg}`"))?
-            {
-                if let Err(err) = self.respond(event_index, log_msg, &entry).with_context(|| {
-                    format!("Failed to respond to `{}", self.events[event_index].name)
-                }) {
-                    warn!("{err:#}");
+            if self.events[event_index].in_grace_period(self.started) {
+                debug!(
+                    "Skip `{}`, it is too soon after start or boot.",
+                    self.events[event_index].name
+                );
+                return Ok(());
+            }
+
+            match self.events[event_index].last_seen.clone() {
+                Some(last_seen) => {
+                    let envs = vec![EnvVar::Custom {
+                        key: "LAST_SEEN".to_string(),
+                        value: realtime_usec(last_seen.realtime).to_string(),
+                    }];
+                    self.fire(event_index, &last_seen, envs, None)?;
+                }
+                // No matching message since monitor is started
+                None => {
+                    let nothing = Entry {
+                        fields: BTreeMap::new(),
+                        realtime: SystemTime::now(),
+                        uptime: None,
+                    };
+                    self.fire(event_index, &nothing, Vec::new(), None)?;
                 }
             }
         }
+
+        Ok(())
     }
 
-    fn respond(
+    /// Execute script of event, and keep the event open until it is resolved
+    fn fire(
         &mut self,
         event_index: usize,
-        log_msg: &str,
-        entry: &BTreeMap<String, String>,
+        entry: &Entry,
+        envs: Vec<EnvVar>,
+        input: Option<String>,
     ) -> Result<()> {
-        if self.events[event_index].in_watch_delay() {
-            debug!(
-                "Skip `{}`, it is still in next watch delay.",
+        if !self.is_active(event_index, Local::now().naive_local()) {
+            match self.events[event_index].off_schedule {
+                OffSchedule::Drop => debug!(
+                    "Skip `{}`, it is outside of active schedule",
+                    self.events[event_index].name
+                ),
+                OffSchedule::Defer => {
+                    debug!(
+                        "Defer `{}` until its schedule is active",
+                        self.events[event_index].name
+                    );
+                    self.events[event_index].defer(entry, envs, input);
+                }
+            }
+            return Ok(());
+        }
+
+        if let Some(condition) = self.events[event_index].unmet_condition() {
+            info!(
+                "Skip `{}`, `{condition}` is not met",
                 self.events[event_index].name
             );
             return Ok(());
         }
 
-        self.events[event_index].record_last_found();
+        let now = Instant::now();
+        let event_quota = self.events[event_index]
+            .quota
+            .as_mut()
+            .map(|quota| quota.available(now))
+            .unwrap_or(true);
+        let global_quota = self
+            .quota
+            .as_mut()
+            .map(|quota| quota.available(now))
+            .unwrap_or(true);
+        if !event_quota || !global_quota {
+            self.events[event_index].suppressed += 1;
+            warn!(
+                "Suppress `{name}`, {scope} max runs is reached ({suppressed} suppressed)",
+                name = self.events[event_index].name,
+                scope = if event_quota { "global" } else { "event" },
+                suppressed = self.events[event_index].suppressed
+            );
+            return Ok(());
+        }
 
         info!(
             "Found EVENT: `{name}`, LOG_MESSAGE: `{log_msg}` => Try to execute `{script}`",
             name = self.events[event_index].name,
+            log_msg = entry.message(),
             script = self.events[event_index].script.display()
         );
 
-        let mut script: Script = Script::new(
+        // Report firings suppressed since the last run
+        let mut envs = envs;
+        if self.events[event_index].suppressed > 0 {
+            envs.push(EnvVar::Custom {
+                key: "SUPPRESSED".to_string(),
+                value: self.events[event_index].suppressed.to_string(),
+            });
+        }
+
+        self.launch(
+            event_index,
             &self.events[event_index].script,
-            self.events[event_index].script_timeout,
-            true,
-        )
-        .context("Failed to prepare script")?;
+            entry,
+            envs,
+            input,
+        )?;
+
+        self.events[event_index].suppressed = 0;
+        if let Some(quota) = self.events[event_index].quota.as_mut() {
+            quota.record(now);
+        }
+        if let Some(quota) = self.quota.as_mut() {
+            quota.record(now);
+        }
+        self.events[event_index].open(entry);
+        Ok(())
+    }
+
+    /// Prepare script of event and put it in launcher's queue
+    fn launch(
+        &self,
+        event_index: usize,
+        script_path: &Path,
+        entry: &Entry,
+        envs: Vec<EnvVar>,
+        input: Option<String>,
+    ) -> Result<()> {
+        let mut script: Script =
+            Script::new(script_path, self.events[event_index].script_timeout, true)
+                .context("Failed to prepare script")?;
 
         // Add JNB_MESSAGE env var
-        let msg_env = EnvVar::Message(log_msg.to_owned());
+        let msg_env = EnvVar::Message(entry.message().to_owned());
         script
             .add_env(msg_env.clone())
             .with_context(|| format!("Could not add env `{msg_env}`"))?;
 
         // Add JNB_JSON env var
-        let json_env = EnvVar::Json(
-            serde_json::to_string(&entry)
-                .with_context(|| format!("Failed to serialize `{entry:?}` to string of JSON"))?,
-        );
+        let json_env = EnvVar::Json(serde_json::to_string(&entry.fields).with_context(|| {
+            format!("Failed to serialize `{:?}` to string of JSON", entry.fields)
+        })?);
         script
             .add_env(json_env.clone())
             .with_context(|| format!("Could not add env `{json_env}`"))?;
 
+        // Add event specific env vars, e.g. JNB_CAP_<NAME>
+        for env in envs {
+            script
+                .add_env(env.clone())
+                .with_context(|| format!("Could not add env `{env}`"))?;
+        }
+
+        if let Some(input) = input {
+            script.set_input(input);
+        }
+        script.set_wait(self.events[event_index].script_wait);
+        script.set_event(&self.events[event_index].name);
+        if let Some(syslog_identifier) = &self.events[event_index].syslog_identifier {
+            script.set_syslog_identifier(syslog_identifier);
+        }
+        if let Some(credentials) = &self.events[event_index].credentials {
+            script.set_credentials(credentials.clone());
+        }
+        let (signal, grace) = self.events[event_index].kill;
+        script.set_kill(signal, grace);
+
         // Put script in launcher's queue
         if let Err(err) = self
             .launcher
-            .add(script.clone())
+            .add(
+                &self.events[event_index].name,
+                self.events[event_index].max_concurrent,
+                script.clone(),
+            )
             .with_context(|| format!("Failed to add script `{script:?}` to launcher"))
         {
             warn!("{err:#}");
@@ -218,23 +1886,814 @@ impl Monitor {
         Ok(())
     }
 
-    fn matches(&self, log_msg: &str) -> Result<Vec<usize>> {
-        let event_regex_set: &RegexSet = {
-            static RE: OnceLock<RegexSet> = OnceLock::new();
-            RE.get_or_init(|| {
-                RegexSet::new(
-                    self.events
-                        .iter()
-                        .map(|event| event.msg_filter.clone())
-                        .collect::<Vec<String>>(),
-                )
-                .expect("Valid regular expressions for matching log message")
+    fn matches(&mut self, log_msg: &str, entry: &BTreeMap<String, String>) -> Result<Vec<Found>> {
+        // Per-event field filters are checked before regular expressions.
+        let candidates: Vec<usize> = self
+            .events
+            .iter()
+            .enumerate()
+            .filter(|(_, event)| event.accepts(entry))
+            .map(|(event_index, _)| event_index)
+            .collect();
+        if candidates.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let matched = self.event_regex_set.matches(log_msg);
+        let mut founds: Vec<Found> = Vec::new();
+        for event_index in candidates {
+            if !matched.matched(event_index) || !self.events[event_index].fields_match(entry) {
+                continue;
+            }
+
+            if self.events[event_index].is_excluded(log_msg) {
+                self.events[event_index].excluded += 1;
+                debug!(
+                    "Exclude `{}`, log message matches exclude pattern ({} times)",
+                    self.events[event_index].name, self.events[event_index].excluded
+                );
+                continue;
+            }
+
+            let captures = self.events[event_index].captures(log_msg);
+            match self.events[event_index].compares(entry, &captures) {
+                Ok(true) => {}
+                Ok(false) => continue,
+                Err(err) => {
+                    warn!(
+                        "Could not compare `{}`, treat as not matched: {err:#}",
+                        self.events[event_index].name
+                    );
+                    continue;
+                }
+            }
+
+            founds.push(Found {
+                event_index,
+                captures,
+            });
+        }
+
+        Ok(founds)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::*;
+
+    fn load_events(config_file: &str) -> Vec<Event> {
+        let mut settings = Settings::new().unwrap();
+        settings.read(config_file).unwrap();
+        settings
+            .events
+            .unwrap()
+            .into_iter()
+            .map(|(name, event)| Event::new(name, event, settings.global.as_ref()).unwrap())
+            .collect()
+    }
+
+    fn load_monitor(config_file: &str) -> Monitor {
+        let mut settings = Settings::new().unwrap();
+        settings.read(config_file).unwrap();
+        Monitor::new(settings).unwrap()
+    }
+
+    fn entry(fields: &[(&str, &str)]) -> BTreeMap<String, String> {
+        fields
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    fn log(message: &str) -> Entry {
+        Entry {
+            fields: entry(&[("MESSAGE", message)]),
+            realtime: UNIX_EPOCH,
+            uptime: None,
+        }
+    }
+
+    #[test]
+    fn test_parse_filter() {
+        assert_eq!(
+            parse_filter("_TRANSPORT=kernel").unwrap(),
+            ("_TRANSPORT", "kernel")
+        );
+        assert_eq!(parse_filter("KEY=a=b").unwrap(), ("KEY", "a=b"));
+        assert!(parse_filter("_TRANSPORT").is_err());
+        assert!(parse_filter("=kernel").is_err());
+    }
+
+    #[test]
+    fn test_event_filters() {
+        let events = load_events(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/settings-6.conf"
+        ));
+        let kernel = events.iter().find(|e| e.name == "kernel-warning").unwrap();
+        let sshd = events.iter().find(|e| e.name == "sshd-unit").unwrap();
+
+        let kernel_warning = entry(&[("_TRANSPORT", "kernel"), ("PRIORITY", "4")]);
+        assert!(kernel.accepts(&kernel_warning));
+        assert!(!sshd.accepts(&kernel_warning));
+
+        // Different fields are AND-ed
+        assert!(!kernel.accepts(&entry(&[("_TRANSPORT", "kernel"), ("PRIORITY", "3")])));
+        assert!(!kernel.accepts(&entry(&[("_TRANSPORT", "kernel")])));
+
+        // Same field is OR-ed
+        assert!(sshd.accepts(&entry(&[("_SYSTEMD_UNIT", "sshd.service")])));
+        assert!(sshd.accepts(&entry(&[("_SYSTEMD_UNIT", "sshd@.service")])));
+        assert!(!sshd.accepts(&entry(&[("_SYSTEMD_UNIT", "cron.service")])));
+    }
+
+    #[test]
+    fn test_event_fields() {
+        let events = load_events(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/settings-7.conf"
+        ));
+        let usb_sshd = events.iter().find(|e| e.name == "usb-sshd").unwrap();
+        let no_fields = events.iter().find(|e| e.name == "no-fields").unwrap();
+
+        assert!(usb_sshd.fields_match(&entry(&[
+            ("SYSLOG_IDENTIFIER", "sshd"),
+            ("_KERNEL_SUBSYSTEM", "usb")
+        ])));
+        assert!(!usb_sshd.fields_match(&entry(&[
+            ("SYSLOG_IDENTIFIER", "sshd-session"),
+            ("_KERNEL_SUBSYSTEM", "usb")
+        ])));
+        assert!(!usb_sshd.fields_match(&entry(&[("SYSLOG_IDENTIFIER", "sshd")])));
+        assert!(no_fields.fields_match(&entry(&[])));
+    }
+
+    #[test]
+    fn test_event_captures() {
+        let events = load_events(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/settings-8.conf"
+        ));
+        let captures =
+            events[0].captures("xhci_hcd 0000:04:00.0: WARN waiting for error on ep to be cleared");
+        assert_eq!(captures.len(), 3);
+        assert_eq!(captures["pci"], "0000:04:00.0");
+        assert_eq!(captures["1"], "0000:04:00.0");
+        assert_eq!(captures["2"], "waiting for error on ep");
+
+        assert!(events[0].captures("usb 1-1: new device").is_empty());
+    }
+
+    #[test]
+    fn test_event_exclude() {
+        let events = load_events(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/settings-9.conf"
+        ));
+        let usb = events.iter().find(|e| e.name == "usb-error").unwrap();
+        assert!(!usb.is_excluded("usb 1-1: device descriptor read/64, error -71"));
+        assert!(usb.is_excluded("usb 1-4: device descriptor read/64, error -71"));
+        assert!(usb.is_excluded("usb 2-4: device not accepting address 5, error -71"));
+        let single = events.iter().find(|e| e.name == "no-exclude").unwrap();
+        assert!(!single.is_excluded("usb 1-4: device descriptor read/64, error -71"));
+    }
+
+    #[test]
+    fn test_event_threshold() {
+        let mut events = load_events(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/settings-10.conf"
+        ));
+        let event = &mut events[0];
+        let at = |secs: u64| UNIX_EPOCH + Duration::from_secs(secs);
+
+        assert_eq!(event.record_threshold(at(0)), None);
+        assert_eq!(event.record_threshold(at(10)), None);
+        // The first match slides out of window
+        assert_eq!(event.record_threshold(at(35)), None);
+        assert_eq!(
+            event.record_threshold(at(36)),
+            Some(ThresholdWindow {
+                count: 3,
+                first: at(10),
+                last: at(36)
             })
+        );
+        // Window is cleared after threshold is reached
+        assert_eq!(event.record_threshold(at(37)), None);
+    }
+
+    #[test]
+    fn test_event_absence() {
+        let mut events = load_events(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/settings-11.conf"
+        ));
+        let event = events.iter_mut().find(|e| e.name == "heartbeat").unwrap();
+        assert_eq!(event.deadline(), None);
+
+        // Armed at start, without any matching entry
+        let before = Instant::now();
+        event.arm();
+        let deadline = event.deadline().unwrap();
+        assert!(deadline >= before + Duration::from_secs(300));
+        assert!(event.disarm(deadline));
+        assert!(event.last_seen.is_none());
+
+        let alive = log("backup-agent: alive");
+        let before = Instant::now();
+        event.see(&alive);
+        let deadline = event.deadline().unwrap();
+        assert!(deadline >= before + Duration::from_secs(300));
+        assert!(deadline <= Instant::now() + Duration::from_secs(300));
+
+        assert!(!event.disarm(Instant::now()));
+        assert!(event.disarm(deadline));
+        assert_eq!(event.deadline(), None);
+        assert!(!event.disarm(deadline));
+        assert_eq!(
+            event.last_seen.as_ref().unwrap().message(),
+            "backup-agent: alive"
+        );
+
+        // Only absence events are armed
+        let other = events.iter_mut().find(|e| e.name != "heartbeat").unwrap();
+        other.arm();
+        assert_eq!(other.deadline(), None);
+
+        // Armed again after firing, while matching message is still missing
+        let mut monitor = load_monitor(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/settings-11.conf"
+        ));
+        let heartbeat = monitor
+            .events
+            .iter()
+            .position(|e| e.name == "heartbeat")
+            .unwrap();
+        monitor.events[heartbeat].arm();
+        let deadline = monitor.events[heartbeat].deadline().unwrap();
+        // Script of test configuration does not exist, only firing matters here.
+        let _ = monitor.on_deadline(heartbeat, deadline);
+        assert!(monitor.events[heartbeat].deadline().unwrap() >= deadline);
+    }
+
+    #[test]
+    fn test_event_sequence() {
+        let mut events = load_events(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/settings-12.conf"
+        ));
+        let sequence = events[0].sequence.as_mut().unwrap();
+        let log = |secs: u64, message: &str| Entry {
+            fields: entry(&[("MESSAGE", message)]),
+            realtime: UNIX_EPOCH + Duration::from_secs(secs),
+            uptime: None,
         };
 
-        Ok(event_regex_set
-            .matches(log_msg)
-            .into_iter()
-            .collect::<Vec<usize>>())
+        // The second step without the first one
+        assert!(sequence
+            .advance(&log(
+                0,
+                "xhci_hcd 0000:04:00.0: WARN waiting for error on ep to be cleared"
+            ))
+            .is_none());
+
+        // Steps of different devices are not correlated
+        assert!(sequence
+            .advance(&log(1, "xhci_hcd 0000:04:00.0: WARN: TRB error"))
+            .is_none());
+        assert!(sequence
+            .advance(&log(
+                2,
+                "xhci_hcd 0000:05:00.0: WARN waiting for error on ep to be cleared"
+            ))
+            .is_none());
+
+        let steps = sequence
+            .advance(&log(
+                3,
+                "xhci_hcd 0000:04:00.0: WARN waiting for error on ep to be cleared",
+            ))
+            .unwrap();
+        assert_eq!(steps.len(), 2);
+        assert_eq!(
+            steps[0].0.message(),
+            "xhci_hcd 0000:04:00.0: WARN: TRB error"
+        );
+        assert_eq!(steps[1].1["pci"], "0000:04:00.0");
+        assert!(sequence.pending.is_empty());
+
+        // Sequence is not completed within window
+        assert!(sequence
+            .advance(&log(10, "xhci_hcd 0000:04:00.0: WARN: TRB error"))
+            .is_none());
+        assert!(sequence
+            .advance(&log(
+                16,
+                "xhci_hcd 0000:04:00.0: WARN waiting for error on ep to be cleared"
+            ))
+            .is_none());
+        assert!(sequence.pending.is_empty());
+    }
+
+    #[test]
+    fn test_event_delay() {
+        let mut events = load_events(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/settings-13.conf"
+        ));
+        let event = &mut events[0];
+
+        let eth0 = log("eth0: Link is Down");
+        assert!(event.postpone(&eth0, &event.captures(eth0.message()), Vec::new()));
+        assert!(!event.postpone(&eth0, &event.captures(eth0.message()), Vec::new()));
+        let eth1 = log("eth1: Link is Down");
+        assert!(event.postpone(&eth1, &event.captures(eth1.message()), Vec::new()));
+        assert_eq!(event.delayed.len(), 2);
+
+        // Recovery message of the other interface
+        assert_eq!(event.cancel("eth2: Link is Up"), 0);
+        assert_eq!(event.cancel("eth0: Link is Up"), 1);
+        assert_eq!(event.delayed.len(), 1);
+
+        // Recovery message without the key does not cancel anything
+        event.cancel_on = Some(Regex::new(r"(?P<iface>\w+)?Link is Up").unwrap());
+        assert_eq!(event.cancel("Link is Up"), 0);
+        assert_eq!(event.delayed.len(), 1);
+
+        assert!(event.take_delayed(Instant::now()).is_empty());
+        let delayed = event.take_delayed(event.deadline().unwrap());
+        assert_eq!(delayed.len(), 1);
+        assert_eq!(delayed[0].entry.message(), "eth1: Link is Down");
+        assert_eq!(event.deadline(), None);
+
+        // Cancel key must be captured by both message and recovery message
+        let link_down = || {
+            let mut settings = Settings::new().unwrap();
+            settings
+                .read(concat!(
+                    env!("CARGO_MANIFEST_DIR"),
+                    "/tests/settings-13.conf"
+                ))
+                .unwrap();
+            settings.events.unwrap().into_iter().next().unwrap()
+        };
+        for cancel_key in ["ifname", "2", "0"] {
+            let (name, mut event) = link_down();
+            event.cancel_key = Some(cancel_key.to_string());
+            assert!(Event::new(name, event, None).is_err());
+        }
+        let (name, mut event) = link_down();
+        event.cancel_on = Some("Link is Up".to_string());
+        assert!(Event::new(name, event, None).is_err());
+    }
+
+    #[test]
+    fn test_event_resolve() {
+        let mut events = load_events(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/settings-14.conf"
+        ));
+        let event = &mut events[0];
+
+        // Not fired yet
+        assert!(event
+            .close("Connection to backup server restored")
+            .is_none());
+
+        event.open(&log("Connection to backup server lost (1)"));
+        event.open(&log("Connection to backup server lost (2)"));
+        assert!(event.close("Connection to backup server lost").is_none());
+        let (trigger, _) = event.close("Connection to backup server restored").unwrap();
+        assert_eq!(trigger.message(), "Connection to backup server lost (1)");
+        assert!(event.opened.is_none());
+    }
+
+    #[test]
+    fn test_recover_scope() {
+        let mut monitor = load_monitor(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/settings-29.conf"
+        ));
+        let index = |monitor: &Monitor, name: &str| {
+            monitor.events.iter().position(|e| e.name == name).unwrap()
+        };
+        let link_down = index(&monitor, "backup-link-down");
+        let server_lost = index(&monitor, "backup-server-lost");
+        let log_of = |unit: &str, message: &str| Entry {
+            fields: entry(&[("MESSAGE", message), ("_SYSTEMD_UNIT", unit)]),
+            realtime: UNIX_EPOCH,
+            uptime: None,
+        };
+
+        let down = log_of("backup.service", "eth0: Link is Down");
+        let captures = monitor.events[link_down].captures(down.message());
+        assert!(monitor.events[link_down].postpone(&down, &captures, Vec::new()));
+        monitor.events[server_lost].open(&log_of(
+            "backup.service",
+            "Connection to backup server lost",
+        ));
+
+        // Recovery messages of another unit are ignored
+        monitor.recover(&log_of("network.service", "eth0: Link is Up"));
+        monitor.recover(&log_of(
+            "other.service",
+            "Connection to backup server restored",
+        ));
+        assert_eq!(monitor.events[link_down].delayed.len(), 1);
+        assert!(monitor.events[server_lost].opened.is_some());
+
+        monitor.recover(&log_of("backup.service", "eth0: Link is Up"));
+        monitor.recover(&log_of(
+            "backup.service",
+            "Connection to backup server restored",
+        ));
+        assert!(monitor.events[link_down].delayed.is_empty());
+        assert!(monitor.events[server_lost].opened.is_none());
+    }
+
+    #[test]
+    fn test_event_throttle_key() {
+        let mut events = load_events(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/settings-15.conf"
+        ));
+        let event = &mut events[0];
+
+        let device_a = log("xhci_hcd 0000:04:00.0: WARN waiting for error on ep to be cleared");
+        let device_b = log("xhci_hcd 0000:05:00.0: WARN waiting for error on ep to be cleared");
+        let key_a = event.throttle_key(&device_a, &event.captures(device_a.message()));
+        let key_b = event.throttle_key(&device_b, &event.captures(device_b.message()));
+        assert_eq!(key_a, "0000:04:00.0");
+        assert_eq!(key_b, "0000:05:00.0");
+
+        assert!(!event.in_watch_delay(&key_a));
+        event.record_last_found(&key_a);
+        assert!(event.in_watch_delay(&key_a));
+        assert!(!event.in_watch_delay(&key_b));
+
+        // The least recently found key is forgotten
+        for index in 0..THROTTLE_KEYS {
+            event.record_last_found(&index.to_string());
+        }
+        assert_eq!(event.last_found.len(), THROTTLE_KEYS);
+        assert!(!event.in_watch_delay(&key_a));
+        assert!(event.in_watch_delay("0"));
+    }
+
+    #[test]
+    fn test_event_debounce() {
+        let mut events = load_events(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/settings-16.conf"
+        ));
+        let event = events.iter_mut().find(|e| e.name == "trailing").unwrap();
+        assert_eq!(event.throttle_mode, ThrottleMode::Trailing);
+
+        let next_watch_delay = event.next_watch_delay.unwrap();
+        event.debounce(String::new(), Captures::new(), &log("burst 1"), Vec::new());
+        let first_deadline = event.deadline().unwrap();
+        event.debounce(String::new(), Captures::new(), &log("burst 2"), Vec::new());
+
+        // Every match pushes the quiet period back to its own time
+        thread::sleep(Duration::from_millis(10));
+        let later = Instant::now();
+        event.debounce(String::new(), Captures::new(), &log("burst 3"), Vec::new());
+        let deadline = event.deadline().unwrap();
+        assert!(deadline > first_deadline);
+        assert!(deadline >= later + next_watch_delay);
+        assert!(deadline <= Instant::now() + next_watch_delay);
+
+        assert!(event
+            .take_debounced(first_deadline - Duration::from_secs(1))
+            .is_empty());
+        let debounced = event.take_debounced(event.deadline().unwrap());
+        assert_eq!(debounced.len(), 1);
+        assert_eq!(debounced[0].1.entry.message(), "burst 3");
+        assert_eq!(debounced[0].1.count, 3);
+        assert_eq!(event.deadline(), None);
+    }
+
+    #[test]
+    fn test_event_aggregate() {
+        let mut events = load_events(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/settings-17.conf"
+        ));
+        let event = events
+            .iter_mut()
+            .find(|e| e.name == "disk-io-error")
+            .unwrap();
+
+        assert!(event.deadline().is_none());
+        for index in 0..5 {
+            event.collect(&log(&format!("I/O error {index}")), Vec::new());
+        }
+        let deadline = event.deadline().unwrap();
+
+        assert!(event.take_aggregated(Instant::now()).is_none());
+        let aggregated = event.take_aggregated(deadline).unwrap();
+        assert_eq!(aggregated.count, 5);
+        assert_eq!(aggregated.entries.len(), 3);
+        assert_eq!(aggregated.entries[0].message(), "I/O error 0");
+        assert_eq!(aggregated.entries[2].message(), "I/O error 2");
+        assert!(event.deadline().is_none());
+
+        let mut settings = Settings::new().unwrap();
+        settings
+            .read(concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/tests/settings-17.conf"
+            ))
+            .unwrap();
+        let (name, mut event) = settings.events.unwrap().into_iter().next().unwrap();
+        event.next_watch_delay = Some(Duration::from_secs(60));
+        assert!(Event::new(name, event, None).is_err());
+    }
+
+    #[test]
+    fn test_quota() {
+        let mut quota = Quota::new(MaxRuns {
+            count: 2,
+            per: Duration::from_secs(3600),
+        });
+        let start = Instant::now();
+        let at = |secs: u64| start + Duration::from_secs(secs);
+
+        assert!(quota.available(at(0)));
+        quota.record(at(0));
+        assert!(quota.available(at(10)));
+        quota.record(at(10));
+        assert!(!quota.available(at(20)));
+        assert!(!quota.available(at(3599)));
+        // The first run slides out of window
+        assert!(quota.available(at(3600)));
+        quota.record(at(3600));
+        assert!(!quota.available(at(3609)));
+    }
+
+    #[test]
+    fn test_event_backoff() {
+        let mut monitor = load_monitor(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/settings-19.conf"
+        ));
+        let delay = |monitor: &Monitor| monitor.events[0].last_found[""].delay;
+        // Script of test configuration does not exist, only next watch delay matters here.
+        let respond = |monitor: &mut Monitor| {
+            let found = Found {
+                event_index: 0,
+                captures: Captures::new(),
+            };
+            let _ = monitor.respond(found, &log("device is not responding"));
+        };
+        let elapse = |monitor: &mut Monitor, secs: u64| {
+            for last_found in monitor.events[0].last_found.values_mut() {
+                last_found.found -= Duration::from_secs(secs);
+                last_found.matched -= Duration::from_secs(secs);
+            }
+        };
+
+        respond(&mut monitor);
+        assert_eq!(delay(&monitor), Duration::from_secs(60));
+
+        // Matches suppressed by next watch delay keep the device failing,
+        // even if next watch delay is longer than reset period.
+        for expected in [180, 540, 600, 600] {
+            let delay_secs = delay(&monitor).as_secs();
+            elapse(&mut monitor, delay_secs - 30);
+            respond(&mut monitor);
+            assert!(monitor.events[0].in_watch_delay(""));
+            elapse(&mut monitor, 60);
+            respond(&mut monitor);
+            assert_eq!(delay(&monitor), Duration::from_secs(expected));
+        }
+
+        // Quiet for reset period after next watch delay
+        elapse(&mut monitor, 600 + 120);
+        respond(&mut monitor);
+        assert_eq!(delay(&monitor), Duration::from_secs(60));
+
+        // Quiet for reset period without suppressed matches
+        elapse(&mut monitor, 90);
+        respond(&mut monitor);
+        assert_eq!(delay(&monitor), Duration::from_secs(180));
+        elapse(&mut monitor, 200);
+        respond(&mut monitor);
+        assert_eq!(delay(&monitor), Duration::from_secs(60));
+
+        let mut settings = Settings::new().unwrap();
+        settings
+            .read(concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/tests/settings-19.conf"
+            ))
+            .unwrap();
+        let (name, mut event) = settings.events.unwrap().into_iter().next().unwrap();
+        event.throttle_mode = ThrottleMode::Trailing;
+        assert!(Event::new(name, event, None).is_err());
+    }
+
+    #[test]
+    fn test_event_dedup() {
+        let events = load_events(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/settings-20.conf"
+        ));
+        let dedup = events[0].dedup.as_ref().unwrap();
+        assert_eq!(dedup.window, Duration::from_secs(600));
+        assert_eq!(
+            dedup.fingerprint(
+                "Job 1a2b3c4d-0000-4fff-8aaa-0123456789ab failed at 0xdeadbeef after 15 retries (pid 4242)"
+            ),
+            "Job <uuid> failed at <hex> after <n> retries"
+        );
+        assert_eq!(
+            dedup.fingerprint("session c0ffee1234 opened for user root"),
+            "session <hex> opened for user root"
+        );
+    }
+
+    #[test]
+    fn test_schedule() {
+        let at = |date: &str| NaiveDateTime::parse_from_str(date, "%Y-%m-%d %H:%M").unwrap();
+        let events = load_events(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/settings-21.conf"
+        ));
+        let active = events[0].active.as_ref().unwrap();
+
+        // 2024-06-01 is Saturday
+        assert!(active.contains(at("2024-06-01 01:00")));
+        assert!(active.contains(at("2024-06-01 04:59")));
+        assert!(!active.contains(at("2024-06-01 05:00")));
+        assert!(!active.contains(at("2024-05-31 01:00")));
+
+        // Overnight range continues to the next day
+        assert!(active.contains(at("2024-06-01 23:30")));
+        assert!(active.contains(at("2024-06-02 00:15")));
+        assert!(active.contains(at("2024-06-03 00:15")));
+        assert!(!active.contains(at("2024-06-03 00:30")));
+        assert!(!active.contains(at("2024-06-01 00:15")));
+
+        let quiet_hours = Schedule::new(settings::Schedule {
+            days: Vec::new(),
+            hours: vec!["22:00-07:00".to_string()],
+        })
+        .unwrap();
+        assert!(quiet_hours.contains(at("2024-06-04 06:59")));
+        assert!(!quiet_hours.contains(at("2024-06-04 07:00")));
+        assert!(quiet_hours.contains(at("2024-06-04 22:00")));
+
+        assert!(Schedule::new(settings::Schedule {
+            days: Vec::new(),
+            hours: vec!["25:00-26:00".to_string()],
+        })
+        .is_err());
+    }
+
+    #[test]
+    fn test_event_startup() {
+        let events = load_events(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/settings-22.conf"
+        ));
+        let event = &events[0];
+        let boot = |secs: Option<u64>| Entry {
+            fields: entry(&[("MESSAGE", "ACPI Error: AE_NOT_FOUND")]),
+            realtime: UNIX_EPOCH,
+            uptime: secs.map(Duration::from_secs),
+        };
+
+        let started = Instant::now();
+        assert!(event.is_too_early(&boot(Some(3600)), started));
+
+        let started = Instant::now() - Duration::from_secs(120);
+        assert!(event.is_too_early(&boot(Some(299)), started));
+        assert!(!event.is_too_early(&boot(Some(300)), started));
+        assert!(!event.is_too_early(&boot(None), started));
+    }
+
+    #[test]
+    fn test_event_absence_ignore_first() {
+        let mut monitor = load_monitor(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/settings-30.conf"
+        ));
+        monitor.events[0].arm();
+        let armed = monitor.events[0].deadline().unwrap();
+
+        // Heartbeat inside `ignore-first` re-arms the event
+        thread::sleep(Duration::from_millis(10));
+        let found = Found {
+            event_index: 0,
+            captures: Captures::new(),
+        };
+        monitor.respond(found, &log("backup-agent: alive")).unwrap();
+        let deadline = monitor.events[0].deadline().unwrap();
+        assert!(deadline > armed);
+        assert!(monitor.events[0].last_seen.is_some());
+
+        // Script of test configuration does not exist, so firing fails.
+        assert!(monitor.on_deadline(0, deadline).is_ok());
+        assert!(monitor.events[0].deadline().unwrap() >= deadline);
+
+        // Missing heartbeat fires after `ignore-first`
+        monitor.started -= Duration::from_secs(600);
+        let deadline = monitor.events[0].deadline().unwrap();
+        assert!(monitor.on_deadline(0, deadline).is_err());
+    }
+
+    #[test]
+    fn test_event_compare() {
+        let events = load_events(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/settings-24.conf"
+        ));
+        let event = &events[0];
+        let compares = |message: &str| {
+            let fields = entry(&[("MESSAGE", message)]);
+            event.compares(&fields, &event.captures(message))
+        };
+
+        assert!(compares("CPU2 temperature above threshold, 95 C").unwrap());
+        assert!(compares("CPU3 temperature above threshold, 90 C").unwrap());
+        assert!(!compares("CPU2 temperature above threshold, 89 C").unwrap());
+        assert!(!compares("CPU4 temperature above threshold, 95 C").unwrap());
+
+        // Value is looked up in journal fields if there is no such capture group
+        let fields = entry(&[("MESSAGE", "CPU1 temperature"), ("temp", "N/A")]);
+        assert!(event.compares(&fields, &Captures::new()).is_err());
+
+        assert!(Compare::new(settings::Compare {
+            name: "temp".to_string(),
+            op: CompareOp::Between,
+            value: CompareValue::Range(vec![10.0, 5.0]),
+        })
+        .is_err());
+        assert!(Compare::new(settings::Compare {
+            name: "temp".to_string(),
+            op: CompareOp::Less,
+            value: CompareValue::Range(vec![5.0, 10.0]),
+        })
+        .is_err());
+    }
+
+    #[test]
+    fn test_event_when() {
+        let events = load_events(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/settings-25.conf"
+        ));
+        let event = &events[0];
+        assert!(event.accepts(&entry(&[("PRIORITY", "3"), ("_COMM", "sshd")])));
+        assert!(event.accepts(&entry(&[
+            ("PRIORITY", "6"),
+            ("MESSAGE", "EXT4-fs error (device sda1)")
+        ])));
+        assert!(!event.accepts(&entry(&[("PRIORITY", "6"), ("MESSAGE", "ok")])));
+        assert!(!event.accepts(&entry(&[("PRIORITY", "3"), ("_COMM", "foo")])));
+    }
+
+    #[test]
+    fn test_event_script_timeout() {
+        let events = load_events(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/settings-27.conf"
+        ));
+        let script_timeout = |name: &str| {
+            events
+                .iter()
+                .find(|e| e.name == name)
+                .unwrap()
+                .script_timeout
+        };
+        assert_eq!(
+            script_timeout("slow-backup"),
+            Some(Duration::from_secs(300))
+        );
+        assert_eq!(script_timeout("long-running"), None);
+        assert_eq!(script_timeout("quick-check"), Some(Duration::from_secs(5)));
+        assert_eq!(
+            script_timeout("default-timeout"),
+            Some(Duration::from_secs(90))
+        );
+        assert_eq!(
+            script_timeout("ping-check"),
+            Some(Duration::from_millis(1500))
+        );
+
+        let kill = |name: &str| events.iter().find(|e| e.name == name).unwrap().kill;
+        assert_eq!(
+            kill("slow-backup"),
+            (libc::SIGTERM, Duration::from_secs(10))
+        );
+        assert_eq!(kill("quick-check"), (libc::SIGTERM, DEFAULT_KILL_GRACE));
+        assert_eq!(kill("ping-check"), (libc::SIGTERM, Duration::from_secs(2)));
+        assert_eq!(kill("long-running"), (libc::SIGKILL, DEFAULT_KILL_GRACE));
     }
 }
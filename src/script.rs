@@ -1,27 +1,27 @@
 use std::{
     collections::HashMap,
     fmt,
-    os::unix::prelude::MetadataExt,
+    io::{BufRead, BufReader, Read, Write},
+    os::unix::{prelude::MetadataExt, process::CommandExt},
     path::{Path, PathBuf},
-    process::Command,
+    process::{Child, Command, Stdio},
     thread,
     time::Duration,
 };
 
 use anyhow::{bail, Context, Result};
+use systemd::{id128::Id128, journal};
 use tracing::{info, warn};
 use wait_timeout::ChildExt;
 
+use crate::credentials::Credentials;
+
 #[derive(Debug, Clone)]
 pub enum EnvVar {
     Message(String),
     Json(String),
-
-    #[allow(dead_code)]
-    Custom {
-        key: String,
-        value: String,
-    },
+    Capture { name: String, value: String },
+    Custom { key: String, value: String },
 }
 
 impl fmt::Display for EnvVar {
@@ -29,20 +29,130 @@ impl fmt::Display for EnvVar {
         match self {
             EnvVar::Message(_) => write!(f, "JNB_MESSAGE"),
             EnvVar::Json(_) => write!(f, "JNB_JSON"),
+            EnvVar::Capture { name, value: _ } => {
+                write!(f, "JNB_CAP_{}", name.to_ascii_uppercase())
+            }
             EnvVar::Custom { key, value: _ } => write!(f, "JNB_{key}"),
         }
     }
 }
 
+/// Signals accepted by `kill-signal`
+const SIGNALS: [(&str, i32); 7] = [
+    ("SIGHUP", libc::SIGHUP),
+    ("SIGINT", libc::SIGINT),
+    ("SIGQUIT", libc::SIGQUIT),
+    ("SIGKILL", libc::SIGKILL),
+    ("SIGUSR1", libc::SIGUSR1),
+    ("SIGUSR2", libc::SIGUSR2),
+    ("SIGTERM", libc::SIGTERM),
+];
+
+/// Signal number of name, e.g. "SIGTERM", "TERM" or "15"
+pub fn parse_signal(name: &str) -> Result<i32> {
+    let name = name.trim().to_ascii_uppercase();
+    SIGNALS
+        .iter()
+        .find(|(signal_name, number)| {
+            *signal_name == name
+                || signal_name.strip_prefix("SIG") == Some(&name)
+                || number.to_string() == name
+        })
+        .map(|(_, number)| *number)
+        .with_context(|| format!("Unsupported signal `{name}`"))
+}
+
+fn signal_name(signal: i32) -> String {
+    SIGNALS
+        .iter()
+        .find(|(_, number)| *number == signal)
+        .map(|(name, _)| name.to_string())
+        .unwrap_or_else(|| format!("signal {signal}"))
+}
+
+/// Time to wait after `kill-signal` before SIGKILL, if `kill-grace` is not set
+pub const DEFAULT_KILL_GRACE: Duration = Duration::from_secs(5);
+
+/// How to stop script after timeout
+#[derive(Debug, Clone, Copy)]
+struct Kill {
+    /// The first signal sent to process group of script
+    signal: i32,
+    /// Time to wait after the first signal before SIGKILL
+    grace: Duration,
+}
+
+/// Journal fields of script output, to find which event and invocation produced it
+#[derive(Debug, Clone)]
+struct Output {
+    event: Option<String>,
+    invocation_id: String,
+    syslog_identifier: String,
+}
+
+impl Output {
+    fn fields(&self, stream: &str, line: &str) -> Vec<String> {
+        let priority = if stream == "stderr" {
+            libc::LOG_ERR
+        } else {
+            libc::LOG_INFO
+        };
+        let mut fields = vec![
+            format!("MESSAGE={line}"),
+            format!("PRIORITY={priority}"),
+            format!("SYSLOG_IDENTIFIER={}", self.syslog_identifier),
+            format!("JNB_INVOCATION_ID={}", self.invocation_id),
+            format!("JNB_STREAM={stream}"),
+        ];
+        if let Some(event) = &self.event {
+            fields.push(format!("JNB_EVENT={event}"));
+        }
+        fields
+    }
+
+    /// Write each line of script output to the journal in another thread.
+    /// The line is logged by daemon if the journal is not available.
+    fn forward(&self, stream: &'static str, reader: impl Read + Send + 'static) {
+        let output = self.clone();
+        thread::spawn(move || {
+            for line in BufReader::new(reader).split(b'\n') {
+                let Ok(line) = line else {
+                    break;
+                };
+                let line = String::from_utf8_lossy(&line);
+                let fields = output.fields(stream, &line);
+                let fields: Vec<&str> = fields.iter().map(String::as_str).collect();
+                if journal::send(&fields) < 0 {
+                    if stream == "stderr" {
+                        warn!("{}: {line}", output.syslog_identifier);
+                    } else {
+                        info!("{}: {line}", output.syslog_identifier);
+                    }
+                }
+            }
+        });
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Script {
     path: PathBuf,
     envs: HashMap<String, String>,
-    timeout: Option<u64>,
+    timeout: Option<Duration>,
+    input: Option<String>,
+    /// Wait until script is finished or timeout before `run` returns
+    wait: bool,
+    kill: Kill,
+    /// Name of event which executes script
+    event: Option<String>,
+    /// `SYSLOG_IDENTIFIER` of script output, file name of script by default
+    syslog_identifier: Option<String>,
+    /// Execute script as another user or group
+    credentials: Option<Credentials>,
 }
 
 impl Script {
-    pub fn new(path: &Path, timeout: Option<u64>, validate: bool) -> Result<Self> {
+    pub fn new(path: &Path, timeout: Option<Duration>, validate: bool) -> Result<Self> {
         if validate {
             Script::validate_script(path)?;
         }
@@ -51,6 +161,15 @@ impl Script {
             path: path.to_path_buf(),
             envs: HashMap::new(),
             timeout,
+            input: None,
+            wait: true,
+            kill: Kill {
+                signal: libc::SIGTERM,
+                grace: DEFAULT_KILL_GRACE,
+            },
+            event: None,
+            syslog_identifier: None,
+            credentials: None,
         })
     }
 
@@ -82,19 +201,74 @@ impl Script {
 
     pub fn add_env(&mut self, env_var: EnvVar) -> Result<()> {
         let value = match &env_var {
-            EnvVar::Message(value) | EnvVar::Json(value) | EnvVar::Custom { key: _, value } => {
-                value
-            }
+            EnvVar::Message(value)
+            | EnvVar::Json(value)
+            | EnvVar::Capture { name: _, value }
+            | EnvVar::Custom { key: _, value } => value,
         };
 
         self.envs.insert(env_var.to_string(), value.to_string());
         Ok(())
     }
 
+    /// Set data written to standard input of script
+    pub fn set_input(&mut self, input: String) {
+        self.input = Some(input);
+    }
+
+    /// Return from `run` as soon as script is started, timeout is still applied in background.
+    pub fn set_wait(&mut self, wait: bool) {
+        self.wait = wait;
+    }
+
+    /// Send `signal` to process group of script after timeout, and SIGKILL if it is still running after `grace`.
+    pub fn set_kill(&mut self, signal: i32, grace: Duration) {
+        self.kill = Kill { signal, grace };
+    }
+
+    /// Set name of event whose output is written to the journal with `JNB_EVENT`
+    pub fn set_event(&mut self, name: &str) {
+        self.event = Some(name.to_string());
+    }
+
+    pub fn set_syslog_identifier(&mut self, syslog_identifier: &str) {
+        self.syslog_identifier = Some(syslog_identifier.to_string());
+    }
+
+    pub fn set_credentials(&mut self, credentials: Credentials) {
+        self.credentials = Some(credentials);
+    }
+
     pub fn run(self) -> Result<()> {
         info!("Execute `{}`", &self.path.display());
-        let mut process = match Command::new(&self.path)
+        let output = Output {
+            event: self.event,
+            invocation_id: Id128::from_random()
+                .map(|id| id.to_string())
+                .unwrap_or_default(),
+            syslog_identifier: self.syslog_identifier.unwrap_or_else(|| {
+                self.path
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_default()
+            }),
+        };
+        let mut command = Command::new(&self.path);
+        if let Some(credentials) = &self.credentials {
+            credentials.apply(&mut command);
+        }
+        let mut process = match command
             .envs(self.envs)
+            .env("JNB_INVOCATION_ID", &output.invocation_id)
+            .stdin(if self.input.is_some() {
+                Stdio::piped()
+            } else {
+                Stdio::inherit()
+            })
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            // Own process group, so that processes started by script can be killed together.
+            .process_group(0)
             .spawn()
             .with_context(|| format!("Failed to execute `{}`", &self.path.display()))
         {
@@ -102,24 +276,34 @@ impl Script {
             Err(err) => bail!("{err:#}"),
         };
 
-        if let Some(timeout) = self.timeout {
-            match process
-                .wait_timeout(Duration::from_secs(timeout))
-                .context("Failed to wait until child process to finish or timeout")?
-            {
-                Some(exit_code) => {
-                    info!("Finished `{}`, {exit_code}", &self.path.display());
-                    return Ok(());
-                }
-                None => {
-                    process.kill()?;
-                    let exit_code = process.wait()?;
-                    bail!(
-                        "Execute timeout `{}`, >= {timeout} seconds, {exit_code}",
-                        &self.path.display()
-                    );
+        if let Some(stdout) = process.stdout.take() {
+            output.forward("stdout", stdout);
+        }
+        if let Some(stderr) = process.stderr.take() {
+            output.forward("stderr", stderr);
+        }
+
+        // Write input in another thread, script may not read all of it.
+        if let (Some(input), Some(mut stdin)) = (self.input, process.stdin.take()) {
+            let path = self.path.clone();
+            thread::spawn(move || {
+                if let Err(err) = stdin.write_all(input.as_bytes()) {
+                    warn!("Failed to write input of `{}`: {err}", path.display());
                 }
+            });
+        }
+
+        if let Some(timeout) = self.timeout {
+            if self.wait {
+                return Script::wait_timeout(&self.path, &mut process, timeout, self.kill);
             }
+
+            thread::spawn(move || {
+                if let Err(err) = Script::wait_timeout(&self.path, &mut process, timeout, self.kill)
+                {
+                    warn!("{err:#}");
+                }
+            });
         } else {
             // Not wait for child process to finish, use thread to wait for child process' return code.
             thread::spawn(move || {
@@ -135,6 +319,60 @@ impl Script {
 
         Ok(())
     }
+
+    /// Wait until child process is finished, or kill its process group after timeout.
+    fn wait_timeout(path: &Path, process: &mut Child, timeout: Duration, kill: Kill) -> Result<()> {
+        match process
+            .wait_timeout(timeout)
+            .context("Failed to wait until child process to finish or timeout")?
+        {
+            Some(exit_code) => {
+                info!("Finished `{}`, {exit_code}", path.display());
+                Ok(())
+            }
+            None => {
+                let mut exit_code = None;
+                if kill.signal != libc::SIGKILL {
+                    info!(
+                        "Send {} to process group of `{}`",
+                        signal_name(kill.signal),
+                        path.display()
+                    );
+                    Script::kill_group(process, kill.signal)?;
+                    exit_code = process
+                        .wait_timeout(kill.grace)
+                        .context("Failed to wait until child process to finish or kill grace")?;
+                }
+
+                // Processes started by script may be still running after script exits.
+                info!("Send SIGKILL to process group of `{}`", path.display());
+                Script::kill_group(process, libc::SIGKILL)?;
+                let exit_code = match exit_code {
+                    Some(exit_code) => exit_code,
+                    None => process.wait()?,
+                };
+                bail!(
+                    "Execute timeout `{}`, >= {} seconds, {exit_code}",
+                    path.display(),
+                    timeout.as_secs_f64()
+                );
+            }
+        }
+    }
+
+    /// Send signal to process group of child process, which is led by the child process.
+    fn kill_group(process: &Child, signal: i32) -> Result<()> {
+        let pgid = process.id() as libc::pid_t;
+        // SAFETY: kill() has no memory safety requirements.
+        if unsafe { libc::kill(-pgid, signal) } != 0 {
+            let err = std::io::Error::last_os_error();
+            // Process group has no process left
+            if err.raw_os_error() != Some(libc::ESRCH) {
+                return Err(err).context("Failed to send signal to process group");
+            }
+        }
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -157,6 +395,7 @@ mod tests {
         let exec_non_root = temp_dir.path().join("executable-non-root-script");
         fs::OpenOptions::new()
             .create(true)
+            .truncate(true)
             .write(true)
             .mode(0o500)
             .open(&exec_non_root)
@@ -167,10 +406,64 @@ mod tests {
         let non_exec_non_root = temp_dir.path().join("non-executable-non-root-script");
         fs::OpenOptions::new()
             .create(true)
+            .truncate(true)
             .write(true)
             .mode(0o400)
             .open(&non_exec_non_root)
             .unwrap();
         assert!(Script::validate_script(Path::new(&non_exec_non_root)).is_err());
     }
+
+    #[test]
+    fn test_env_var_name() {
+        assert_eq!(EnvVar::Message(String::new()).to_string(), "JNB_MESSAGE");
+        assert_eq!(EnvVar::Json(String::new()).to_string(), "JNB_JSON");
+        assert_eq!(
+            EnvVar::Capture {
+                name: "pci".to_string(),
+                value: String::new()
+            }
+            .to_string(),
+            "JNB_CAP_PCI"
+        );
+        assert_eq!(
+            EnvVar::Capture {
+                name: "1".to_string(),
+                value: String::new()
+            }
+            .to_string(),
+            "JNB_CAP_1"
+        );
+    }
+
+    #[test]
+    fn test_parse_signal() {
+        assert_eq!(parse_signal("SIGTERM").unwrap(), libc::SIGTERM);
+        assert_eq!(parse_signal("term").unwrap(), libc::SIGTERM);
+        assert_eq!(parse_signal("9").unwrap(), libc::SIGKILL);
+        assert_eq!(parse_signal("SIGUSR1").unwrap(), libc::SIGUSR1);
+        assert!(parse_signal("SIGFOO").is_err());
+        assert_eq!(signal_name(libc::SIGINT), "SIGINT");
+    }
+
+    #[test]
+    fn test_output_fields() {
+        let output = Output {
+            event: Some("xhci_hcd-error".to_string()),
+            invocation_id: "0123456789abcdef0123456789abcdef".to_string(),
+            syslog_identifier: "xhci_hcd-rebind.sh".to_string(),
+        };
+        assert_eq!(
+            output.fields("stderr", "rebind failed"),
+            vec![
+                "MESSAGE=rebind failed",
+                "PRIORITY=3",
+                "SYSLOG_IDENTIFIER=xhci_hcd-rebind.sh",
+                "JNB_INVOCATION_ID=0123456789abcdef0123456789abcdef",
+                "JNB_STREAM=stderr",
+                "JNB_EVENT=xhci_hcd-error",
+            ]
+        );
+        assert_eq!(output.fields("stdout", "done")[1], "PRIORITY=6");
+    }
 }
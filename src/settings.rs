@@ -2,12 +2,54 @@ use std::time::Duration;
 
 use anyhow::{anyhow, Context, Result};
 use config::{builder::DefaultState, Config, ConfigBuilder, FileFormat, Map};
-use serde::Deserialize;
+use serde::{de, Deserialize, Deserializer};
+
+use crate::expr::Expr;
 
 const fn default_true() -> Option<bool> {
     Some(true)
 }
 
+/// Script timeout in seconds or human-readable duration, e.g. 20, "20", "90s", "5 min".
+/// "none" means no timeout.
+fn deserialize_script_timeout<'de, D>(deserializer: D) -> Result<Option<Duration>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Timeout {
+        Seconds(u64),
+        Text(String),
+    }
+
+    match Timeout::deserialize(deserializer)? {
+        Timeout::Seconds(secs) => Ok(Some(Duration::from_secs(secs))),
+        Timeout::Text(text) => {
+            let text = text.trim();
+            if text == "none" {
+                return Ok(None);
+            }
+            if let Ok(secs) = text.parse::<u64>() {
+                return Ok(Some(Duration::from_secs(secs)));
+            }
+            humantime::parse_duration(text)
+                .map(Some)
+                .map_err(de::Error::custom)
+        }
+    }
+}
+
+/// Per-event script timeout, `None` if it is not set.
+fn deserialize_event_script_timeout<'de, D>(
+    deserializer: D,
+) -> Result<Option<Option<Duration>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserialize_script_timeout(deserializer).map(Some)
+}
+
 #[derive(Debug, Deserialize)]
 pub struct Settings {
     #[serde(skip_deserializing)]
@@ -22,15 +64,222 @@ pub struct Global {
     #[serde(default)]
     pub filters: Option<Vec<String>>,
 
+    #[serde(default, deserialize_with = "deserialize_script_timeout")]
+    pub script_timeout: Option<Duration>,
+
+    #[serde(default, rename(deserialize = "max-runs"))]
+    pub max_runs: Option<MaxRuns>,
+
+    #[serde(default, rename(deserialize = "quiet-hours"))]
+    pub quiet_hours: Option<Schedule>,
+
+    #[serde(default, rename(deserialize = "max-concurrent"))]
+    pub max_concurrent: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub struct MaxRuns {
+    pub count: usize,
+
+    #[serde(with = "humantime_serde")]
+    pub per: Duration,
+}
+
+#[derive(Debug, PartialEq, Eq, Deserialize)]
+pub struct Threshold {
+    pub count: usize,
+
+    #[serde(with = "humantime_serde")]
+    pub within: Duration,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EventKind {
+    /// Fire when message is found
+    #[default]
+    Match,
+
+    /// Fire when message is not found within `expect-every`
+    Absence,
+
+    /// Fire when messages of all `steps` are found in order within `within`
+    Sequence,
+}
+
+const fn default_aggregate_max() -> usize {
+    1000
+}
+
+#[derive(Debug, PartialEq, Eq, Deserialize)]
+pub struct Aggregate {
+    #[serde(with = "humantime_serde")]
+    pub window: Duration,
+
+    #[serde(default = "default_aggregate_max")]
+    pub max: usize,
+}
+
+const fn default_backoff_multiplier() -> f64 {
+    2.0
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+pub struct Backoff {
+    #[serde(default = "default_backoff_multiplier")]
+    pub multiplier: f64,
+
+    #[serde(rename(deserialize = "max-delay"), with = "humantime_serde")]
+    pub max_delay: Duration,
+
+    #[serde(rename(deserialize = "reset-after"), with = "humantime_serde")]
+    pub reset_after: Duration,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Normalize {
+    /// Replace numbers with `<n>`
+    Digits,
+
+    /// Replace `0x` prefixed or long hexadecimal numbers with `<hex>`
+    Hex,
+
+    /// Replace UUIDs with `<uuid>`
+    Uuid,
+}
+
+#[derive(Debug, PartialEq, Eq, Deserialize)]
+pub struct Replace {
+    pub pattern: String,
+
     #[serde(default)]
-    pub script_timeout: Option<u64>,
+    pub with: String,
+}
+
+#[derive(Debug, PartialEq, Eq, Deserialize)]
+pub struct Dedup {
+    #[serde(with = "humantime_serde")]
+    pub window: Duration,
+
+    #[serde(default)]
+    pub normalize: Vec<Normalize>,
+
+    #[serde(default)]
+    pub replace: Vec<Replace>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum CompareOp {
+    #[serde(rename = ">")]
+    Greater,
+
+    #[serde(rename = ">=")]
+    GreaterOrEqual,
+
+    #[serde(rename = "<")]
+    Less,
+
+    #[serde(rename = "<=")]
+    LessOrEqual,
+
+    /// Inclusive range, `value = [min, max]`
+    #[serde(rename = "between")]
+    Between,
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum CompareValue {
+    Number(f64),
+    Range(Vec<f64>),
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+pub struct Compare {
+    /// Capture group name or journal field name
+    pub name: String,
+    pub op: CompareOp,
+    pub value: CompareValue,
+}
+
+#[derive(Debug, PartialEq, Eq, Deserialize)]
+pub struct FileContains {
+    pub path: String,
+    pub pattern: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Weekday {
+    Mon,
+    Tue,
+    Wed,
+    Thu,
+    Fri,
+    Sat,
+    Sun,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Schedule {
+    /// Days of week, every day if empty
+    #[serde(default)]
+    pub days: Vec<Weekday>,
+
+    /// Time ranges in local time, e.g. "22:00-06:00", all day if empty
+    #[serde(default)]
+    pub hours: Vec<String>,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OffSchedule {
+    /// Skip matches found outside schedule
+    #[default]
+    Drop,
+
+    /// Hold the last match found outside schedule until schedule is active again
+    Defer,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ThrottleMode {
+    /// Execute script with the first match, and skip the others within `next-watch-delay`
+    #[default]
+    Leading,
+
+    /// Execute script with the last match, after matches are quiet for `next-watch-delay`
+    Trailing,
+
+    /// Both leading and trailing
+    Both,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct Event {
+    #[serde(default)]
+    pub kind: EventKind,
+
+    #[serde(default)]
+    pub filters: Option<Vec<String>>,
+
     #[serde(default)]
     pub message: String,
 
+    #[serde(default)]
+    pub fields: Option<Map<String, String>>,
+
+    #[serde(default)]
+    pub exclude: Option<Vec<String>>,
+
+    #[serde(default)]
+    pub compare: Option<Vec<Compare>>,
+
+    #[serde(default)]
+    pub when: Option<Expr>,
+
     #[serde(
         default,
         rename(deserialize = "next-watch-delay"),
@@ -38,11 +287,116 @@ pub struct Event {
     )]
     pub next_watch_delay: Option<Duration>,
 
+    #[serde(default, rename(deserialize = "throttle-key"))]
+    pub throttle_key: Option<String>,
+
+    #[serde(default, rename(deserialize = "throttle-mode"))]
+    pub throttle_mode: ThrottleMode,
+
+    #[serde(default)]
+    pub backoff: Option<Backoff>,
+
+    #[serde(default)]
+    pub threshold: Option<Threshold>,
+
+    #[serde(
+        default,
+        rename(deserialize = "expect-every"),
+        with = "humantime_serde"
+    )]
+    pub expect_every: Option<Duration>,
+
+    #[serde(default)]
+    pub steps: Option<Vec<String>>,
+
+    #[serde(default, with = "humantime_serde")]
+    pub within: Option<Duration>,
+
+    #[serde(default)]
+    pub correlate: Option<String>,
+
+    #[serde(default, with = "humantime_serde")]
+    pub delay: Option<Duration>,
+
+    #[serde(default, rename(deserialize = "cancel-on"))]
+    pub cancel_on: Option<String>,
+
+    #[serde(default, rename(deserialize = "cancel-key"))]
+    pub cancel_key: Option<String>,
+
+    #[serde(default)]
+    pub dedup: Option<Dedup>,
+
+    #[serde(default)]
+    pub aggregate: Option<Aggregate>,
+
+    #[serde(default, rename(deserialize = "max-runs"))]
+    pub max_runs: Option<MaxRuns>,
+
+    #[serde(
+        default,
+        rename(deserialize = "ignore-first"),
+        with = "humantime_serde"
+    )]
+    pub ignore_first: Option<Duration>,
+
+    #[serde(default, rename(deserialize = "min-uptime"), with = "humantime_serde")]
+    pub min_uptime: Option<Duration>,
+
+    #[serde(default)]
+    pub active: Option<Schedule>,
+
+    #[serde(default, rename(deserialize = "off-schedule"))]
+    pub off_schedule: OffSchedule,
+
+    #[serde(default, rename(deserialize = "condition-path-exists"))]
+    pub condition_path_exists: Option<Vec<String>>,
+
+    #[serde(default, rename(deserialize = "condition-unit-active"))]
+    pub condition_unit_active: Option<Vec<String>>,
+
+    #[serde(default, rename(deserialize = "condition-file-contains"))]
+    pub condition_file_contains: Option<Vec<FileContains>>,
+
+    #[serde(default, rename(deserialize = "resolve-on"))]
+    pub resolve_on: Option<String>,
+
+    #[serde(default, rename(deserialize = "on-resolve"))]
+    pub on_resolve: Option<String>,
+
     #[serde(default)]
     pub script: String,
 
+    #[serde(
+        default,
+        rename(deserialize = "script-timeout"),
+        deserialize_with = "deserialize_event_script_timeout"
+    )]
+    pub script_timeout: Option<Option<Duration>>,
+
+    #[serde(default, rename(deserialize = "kill-signal"))]
+    pub kill_signal: Option<String>,
+
+    #[serde(default, rename(deserialize = "kill-grace"), with = "humantime_serde")]
+    pub kill_grace: Option<Duration>,
+
     #[serde(default = "default_true", rename(deserialize = "script-wait"))]
     pub script_wait: Option<bool>,
+
+    #[serde(default, rename(deserialize = "syslog-identifier"))]
+    pub syslog_identifier: Option<String>,
+
+    #[serde(default)]
+    pub user: Option<String>,
+
+    #[serde(default)]
+    pub group: Option<String>,
+
+    #[serde(default, rename(deserialize = "supplementary-groups"))]
+    pub supplementary_groups: Option<Vec<String>>,
+
+    #[serde(default, rename(deserialize = "max-concurrent"))]
+    pub max_concurrent: Option<usize>,
 }
 
 impl Settings {
@@ -97,7 +451,10 @@ mod tests {
                 "/tests/settings-1.conf"
             ))
             .unwrap();
-        assert_eq!(settings.global.as_ref().unwrap().script_timeout, Some(20));
+        assert_eq!(
+            settings.global.as_ref().unwrap().script_timeout,
+            Some(Duration::from_secs(20))
+        );
         assert_eq!(
             settings.events.as_ref().unwrap()["event-1"].message,
             "regex-1"
@@ -122,7 +479,10 @@ mod tests {
                 "/tests/settings-2.conf"
             ))
             .unwrap();
-        assert_eq!(settings.global.as_ref().unwrap().script_timeout, Some(20));
+        assert_eq!(
+            settings.global.as_ref().unwrap().script_timeout,
+            Some(Duration::from_secs(20))
+        );
         assert_eq!(
             settings.events.as_ref().unwrap()["event-2"].message,
             "regex-2"
@@ -149,7 +509,10 @@ mod tests {
                 "/tests/settings-3.conf"
             ))
             .unwrap();
-        assert_eq!(settings.global.as_ref().unwrap().script_timeout, Some(20));
+        assert_eq!(
+            settings.global.as_ref().unwrap().script_timeout,
+            Some(Duration::from_secs(20))
+        );
         assert_eq!(
             settings.events.as_ref().unwrap()["event-3"].message,
             "regex-3"
@@ -174,7 +537,10 @@ mod tests {
                 "/tests/settings-4.conf"
             ))
             .unwrap();
-        assert_eq!(settings.global.as_ref().unwrap().script_timeout, Some(10));
+        assert_eq!(
+            settings.global.as_ref().unwrap().script_timeout,
+            Some(Duration::from_secs(10))
+        );
         assert_eq!(
             settings.events.as_ref().unwrap()["event-4"].message,
             "regex-4"
@@ -205,7 +571,10 @@ mod tests {
             settings.global.as_ref().unwrap().filters.as_ref().unwrap(),
             &vec!["_TRANSPORT=kernel", "PRIORITY=4"]
         );
-        assert_eq!(settings.global.as_ref().unwrap().script_timeout, Some(20));
+        assert_eq!(
+            settings.global.as_ref().unwrap().script_timeout,
+            Some(Duration::from_secs(20))
+        );
         assert_eq!(
             settings.events.as_ref().unwrap()["xhci_hcd-error"].message,
             "xhci_hcd 0000:04:00\\.0: WARN waiting for error on ep to be cleared"
@@ -225,6 +594,535 @@ mod tests {
             .unwrap());
     }
 
+    #[test]
+    fn load_event_filters() {
+        let mut settings = Settings::new().unwrap();
+        settings
+            .read(concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/tests/settings-6.conf"
+            ))
+            .unwrap();
+        assert!(settings.global.as_ref().unwrap().filters.is_none());
+        assert_eq!(
+            settings.events.as_ref().unwrap()["kernel-warning"]
+                .filters
+                .as_ref()
+                .unwrap(),
+            &vec!["_TRANSPORT=kernel", "PRIORITY=4"]
+        );
+        assert_eq!(
+            settings.events.as_ref().unwrap()["sshd-unit"]
+                .filters
+                .as_ref()
+                .unwrap(),
+            &vec!["_SYSTEMD_UNIT=sshd.service", "_SYSTEMD_UNIT=sshd@.service"]
+        );
+    }
+
+    #[test]
+    fn load_event_fields() {
+        let mut settings = Settings::new().unwrap();
+        settings
+            .read(concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/tests/settings-7.conf"
+            ))
+            .unwrap();
+        let fields = settings.events.as_ref().unwrap()["usb-sshd"]
+            .fields
+            .as_ref()
+            .unwrap();
+        assert_eq!(fields.len(), 2);
+        // Keys are lowercased by configuration loader
+        assert_eq!(fields["syslog_identifier"], "^sshd$");
+        assert_eq!(fields["_kernel_subsystem"], "usb");
+        assert!(settings.events.as_ref().unwrap()["no-fields"]
+            .fields
+            .is_none());
+    }
+
+    #[test]
+    fn load_event_exclude() {
+        let mut settings = Settings::new().unwrap();
+        settings
+            .read(concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/tests/settings-9.conf"
+            ))
+            .unwrap();
+        assert_eq!(
+            settings.events.as_ref().unwrap()["usb-error"]
+                .exclude
+                .as_ref()
+                .unwrap(),
+            &vec!["^usb 1-4:", "^usb 2-4:"]
+        );
+        assert!(settings.events.as_ref().unwrap()["no-exclude"]
+            .exclude
+            .is_none());
+    }
+
+    #[test]
+    fn load_event_threshold() {
+        let mut settings = Settings::new().unwrap();
+        settings
+            .read(concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/tests/settings-10.conf"
+            ))
+            .unwrap();
+        assert_eq!(
+            settings.events.as_ref().unwrap()["link-flapping"]
+                .threshold
+                .as_ref()
+                .unwrap(),
+            &Threshold {
+                count: 3,
+                within: Duration::from_secs(30)
+            }
+        );
+    }
+
+    #[test]
+    fn load_event_kind() {
+        let mut settings = Settings::new().unwrap();
+        settings
+            .read(concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/tests/settings-11.conf"
+            ))
+            .unwrap();
+        assert_eq!(
+            settings.events.as_ref().unwrap()["heartbeat"].kind,
+            EventKind::Absence
+        );
+        assert_eq!(
+            settings.events.as_ref().unwrap()["heartbeat"]
+                .expect_every
+                .unwrap(),
+            Duration::from_secs(300)
+        );
+        assert_eq!(
+            settings.events.as_ref().unwrap()["match-event"].kind,
+            EventKind::Match
+        );
+        assert!(settings.events.as_ref().unwrap()["match-event"]
+            .expect_every
+            .is_none());
+    }
+
+    #[test]
+    fn load_event_sequence() {
+        let mut settings = Settings::new().unwrap();
+        settings
+            .read(concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/tests/settings-12.conf"
+            ))
+            .unwrap();
+        let event = &settings.events.as_ref().unwrap()["xhci_hcd-stuck"];
+        assert_eq!(event.kind, EventKind::Sequence);
+        assert_eq!(
+            event.steps.as_ref().unwrap(),
+            &vec![
+                "xhci_hcd (?P<pci>[0-9a-f:.]+): WARN: TRB error",
+                "xhci_hcd (?P<pci>[0-9a-f:.]+): WARN waiting for error on ep to be cleared"
+            ]
+        );
+        assert_eq!(event.within.unwrap(), Duration::from_secs(5));
+        assert_eq!(event.correlate.as_ref().unwrap(), "pci");
+    }
+
+    #[test]
+    fn load_event_delay() {
+        let mut settings = Settings::new().unwrap();
+        settings
+            .read(concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/tests/settings-13.conf"
+            ))
+            .unwrap();
+        let event = &settings.events.as_ref().unwrap()["link-down"];
+        assert_eq!(event.delay.unwrap(), Duration::from_secs(10));
+        assert_eq!(
+            event.cancel_on.as_ref().unwrap(),
+            "(?P<iface>\\w+): Link is Up"
+        );
+        assert_eq!(event.cancel_key.as_ref().unwrap(), "iface");
+    }
+
+    #[test]
+    fn load_event_resolve() {
+        let mut settings = Settings::new().unwrap();
+        settings
+            .read(concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/tests/settings-14.conf"
+            ))
+            .unwrap();
+        let event = &settings.events.as_ref().unwrap()["backup-server-lost"];
+        assert_eq!(
+            event.resolve_on.as_ref().unwrap(),
+            "Connection to backup server restored"
+        );
+        assert_eq!(
+            event.on_resolve.as_ref().unwrap(),
+            "/usr/local/bin/page-resolve.sh"
+        );
+    }
+
+    #[test]
+    fn load_event_throttle_key() {
+        let mut settings = Settings::new().unwrap();
+        settings
+            .read(concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/tests/settings-15.conf"
+            ))
+            .unwrap();
+        let event = &settings.events.as_ref().unwrap()["xhci_hcd-error"];
+        assert_eq!(event.next_watch_delay.unwrap(), Duration::from_secs(60));
+        assert_eq!(event.throttle_key.as_ref().unwrap(), "pci");
+    }
+
+    #[test]
+    fn load_event_throttle_mode() {
+        let mut settings = Settings::new().unwrap();
+        settings
+            .read(concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/tests/settings-16.conf"
+            ))
+            .unwrap();
+        let events = settings.events.as_ref().unwrap();
+        assert_eq!(events["leading"].throttle_mode, ThrottleMode::Leading);
+        assert_eq!(events["trailing"].throttle_mode, ThrottleMode::Trailing);
+        assert_eq!(events["both"].throttle_mode, ThrottleMode::Both);
+    }
+
+    #[test]
+    fn load_event_aggregate() {
+        let mut settings = Settings::new().unwrap();
+        settings
+            .read(concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/tests/settings-17.conf"
+            ))
+            .unwrap();
+        let events = settings.events.as_ref().unwrap();
+        assert_eq!(
+            events["disk-io-error"].aggregate.as_ref().unwrap(),
+            &Aggregate {
+                window: Duration::from_secs(10),
+                max: 3
+            }
+        );
+        assert_eq!(
+            events["default-max"].aggregate.as_ref().unwrap(),
+            &Aggregate {
+                window: Duration::from_secs(60),
+                max: 1000
+            }
+        );
+    }
+
+    #[test]
+    fn load_max_runs() {
+        let mut settings = Settings::new().unwrap();
+        settings
+            .read(concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/tests/settings-18.conf"
+            ))
+            .unwrap();
+        assert_eq!(
+            settings.global.as_ref().unwrap().max_runs.unwrap(),
+            MaxRuns {
+                count: 20,
+                per: Duration::from_secs(3600)
+            }
+        );
+        assert_eq!(
+            settings.events.as_ref().unwrap()["reboot-device"]
+                .max_runs
+                .unwrap(),
+            MaxRuns {
+                count: 3,
+                per: Duration::from_secs(3600)
+            }
+        );
+    }
+
+    #[test]
+    fn load_event_backoff() {
+        let mut settings = Settings::new().unwrap();
+        settings
+            .read(concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/tests/settings-19.conf"
+            ))
+            .unwrap();
+        let events = settings.events.as_ref().unwrap();
+        assert_eq!(
+            events["rebind-device"].backoff.as_ref().unwrap(),
+            &Backoff {
+                multiplier: 3.0,
+                max_delay: Duration::from_secs(600),
+                reset_after: Duration::from_secs(120)
+            }
+        );
+    }
+
+    #[test]
+    fn load_event_dedup() {
+        let mut settings = Settings::new().unwrap();
+        settings
+            .read(concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/tests/settings-20.conf"
+            ))
+            .unwrap();
+        assert_eq!(
+            settings.events.as_ref().unwrap()["job-failed"]
+                .dedup
+                .as_ref()
+                .unwrap(),
+            &Dedup {
+                window: Duration::from_secs(600),
+                normalize: vec![Normalize::Digits, Normalize::Hex, Normalize::Uuid],
+                replace: vec![Replace {
+                    pattern: " \\(pid [^)]*\\)".to_string(),
+                    with: String::new()
+                }]
+            }
+        );
+    }
+
+    #[test]
+    fn load_schedule() {
+        let mut settings = Settings::new().unwrap();
+        settings
+            .read(concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/tests/settings-21.conf"
+            ))
+            .unwrap();
+        assert_eq!(
+            settings
+                .global
+                .as_ref()
+                .unwrap()
+                .quiet_hours
+                .as_ref()
+                .unwrap(),
+            &Schedule {
+                days: Vec::new(),
+                hours: vec!["22:00-07:00".to_string()]
+            }
+        );
+
+        let event = &settings.events.as_ref().unwrap()["restart-service"];
+        assert_eq!(
+            event.active.as_ref().unwrap(),
+            &Schedule {
+                days: vec![Weekday::Sat, Weekday::Sun],
+                hours: vec!["01:00-05:00".to_string(), "23:00-00:30".to_string()]
+            }
+        );
+        assert_eq!(event.off_schedule, OffSchedule::Defer);
+    }
+
+    #[test]
+    fn load_event_startup() {
+        let mut settings = Settings::new().unwrap();
+        settings
+            .read(concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/tests/settings-22.conf"
+            ))
+            .unwrap();
+        let event = &settings.events.as_ref().unwrap()["acpi-error"];
+        assert_eq!(event.ignore_first, Some(Duration::from_secs(120)));
+        assert_eq!(event.min_uptime, Some(Duration::from_secs(300)));
+    }
+
+    #[test]
+    fn load_event_conditions() {
+        let mut settings = Settings::new().unwrap();
+        settings
+            .read(concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/tests/settings-23.conf"
+            ))
+            .unwrap();
+        let event = &settings.events.as_ref().unwrap()["rebind-xhci"];
+        assert_eq!(
+            event.condition_path_exists,
+            Some(vec![
+                "/sys/bus/pci/devices/0000:04:00.0".to_string(),
+                "!/run/rebind-xhci.lock".to_string()
+            ])
+        );
+        assert_eq!(
+            event.condition_unit_active,
+            Some(vec!["NetworkManager.service".to_string()])
+        );
+        assert_eq!(
+            event.condition_file_contains,
+            Some(vec![FileContains {
+                path: "/sys/bus/pci/devices/0000:04:00.0/power/runtime_status".to_string(),
+                pattern: "^active".to_string()
+            }])
+        );
+    }
+
+    #[test]
+    fn load_event_compare() {
+        let mut settings = Settings::new().unwrap();
+        settings
+            .read(concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/tests/settings-24.conf"
+            ))
+            .unwrap();
+        assert_eq!(
+            settings.events.as_ref().unwrap()["cpu-overheat"].compare,
+            Some(vec![
+                Compare {
+                    name: "temp".to_string(),
+                    op: CompareOp::GreaterOrEqual,
+                    value: CompareValue::Number(90.0)
+                },
+                Compare {
+                    name: "cpu".to_string(),
+                    op: CompareOp::Between,
+                    value: CompareValue::Range(vec![0.0, 3.5])
+                }
+            ])
+        );
+    }
+
+    #[test]
+    fn load_event_when() {
+        let mut settings = Settings::new().unwrap();
+        settings
+            .read(concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/tests/settings-25.conf"
+            ))
+            .unwrap();
+        let when = settings.events.as_ref().unwrap()["critical"]
+            .when
+            .as_ref()
+            .unwrap();
+        let entry = |fields: &[(&str, &str)]| {
+            fields
+                .iter()
+                .map(|(key, val)| (key.to_string(), val.to_string()))
+                .collect()
+        };
+        assert!(when.eval(&entry(&[("PRIORITY", "2"), ("_COMM", "sshd")])));
+        assert!(!when.eval(&entry(&[("PRIORITY", "2"), ("_COMM", "foo")])));
+
+        // Invalid expression is reported when configuration is loaded
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let config_file = temp_dir.path().join("invalid.conf");
+        std::fs::write(
+            &config_file,
+            "[events.critical]\nmessage = '.*'\nwhen = \"PRIORITY<=high\"\nscript = \"script-25\"\n",
+        )
+        .unwrap();
+        let mut settings = Settings::new().unwrap();
+        assert!(settings.read(config_file.to_str().unwrap()).is_err());
+    }
+
+    #[test]
+    fn load_max_concurrent() {
+        let mut settings = Settings::new().unwrap();
+        settings
+            .read(concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/tests/settings-26.conf"
+            ))
+            .unwrap();
+        assert_eq!(settings.global.as_ref().unwrap().max_concurrent, Some(8));
+
+        let events = settings.events.as_ref().unwrap();
+        assert_eq!(events["reboot-device"].max_concurrent, Some(1));
+        assert_eq!(events["reboot-device"].script_wait, Some(true));
+        assert_eq!(events["send-notification"].max_concurrent, None);
+        assert_eq!(events["send-notification"].script_wait, Some(false));
+        assert_eq!(
+            events["send-notification"].syslog_identifier,
+            Some("notify".to_string())
+        );
+        assert_eq!(events["reboot-device"].syslog_identifier, None);
+    }
+
+    #[test]
+    fn load_script_timeout() {
+        let mut settings = Settings::new().unwrap();
+        settings
+            .read(concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/tests/settings-27.conf"
+            ))
+            .unwrap();
+        assert_eq!(
+            settings.global.as_ref().unwrap().script_timeout,
+            Some(Duration::from_secs(90))
+        );
+
+        let events = settings.events.as_ref().unwrap();
+        assert_eq!(
+            events["slow-backup"].script_timeout,
+            Some(Some(Duration::from_secs(300)))
+        );
+        assert_eq!(events["long-running"].script_timeout, Some(None));
+        assert_eq!(
+            events["quick-check"].script_timeout,
+            Some(Some(Duration::from_secs(5)))
+        );
+        assert_eq!(events["default-timeout"].script_timeout, None);
+        assert_eq!(
+            events["ping-check"].script_timeout,
+            Some(Some(Duration::from_millis(1500)))
+        );
+        assert_eq!(events["ping-check"].kill_signal, None);
+        assert_eq!(
+            events["ping-check"].kill_grace,
+            Some(Duration::from_secs(2))
+        );
+
+        assert_eq!(
+            events["slow-backup"].kill_signal,
+            Some("SIGTERM".to_string())
+        );
+        assert_eq!(
+            events["slow-backup"].kill_grace,
+            Some(Duration::from_secs(10))
+        );
+    }
+
+    #[test]
+    fn load_event_credentials() {
+        let mut settings = Settings::new().unwrap();
+        settings
+            .read(concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/tests/settings-28.conf"
+            ))
+            .unwrap();
+        let event = &settings.events.as_ref().unwrap()["send-notification"];
+        assert_eq!(event.user, Some("nobody".to_string()));
+        assert_eq!(event.group, Some("nogroup".to_string()));
+        assert_eq!(
+            event.supplementary_groups,
+            Some(vec!["systemd-journal".to_string(), "adm".to_string()])
+        );
+    }
+
     #[test]
     fn load_settings_example() {
         let mut settings = Settings::new().unwrap();
@@ -239,14 +1137,20 @@ mod tests {
             .read(concat!(env!("CARGO_MANIFEST_DIR"), "/00-template.conf"))
             .unwrap();
 
-        assert_eq!(settings.global.as_ref().unwrap().script_timeout, Some(20));
+        assert_eq!(
+            settings.global.as_ref().unwrap().script_timeout,
+            Some(Duration::from_secs(20))
+        );
         assert!(settings.events.is_none());
     }
 
     #[test]
     fn load_multiple_configs_in_order() {
         let mut settings = Settings::new().unwrap();
-        assert_eq!(settings.global.as_ref().unwrap().script_timeout, Some(20));
+        assert_eq!(
+            settings.global.as_ref().unwrap().script_timeout,
+            Some(Duration::from_secs(20))
+        );
         assert!(settings.events.is_none());
 
         // settings-1
@@ -256,7 +1160,10 @@ mod tests {
                 "/tests/settings-1.conf"
             ))
             .unwrap();
-        assert_eq!(settings.global.as_ref().unwrap().script_timeout, Some(20));
+        assert_eq!(
+            settings.global.as_ref().unwrap().script_timeout,
+            Some(Duration::from_secs(20))
+        );
         assert_eq!(settings.events.as_ref().unwrap().len(), 1);
         assert_eq!(
             settings.events.as_ref().unwrap()["event-1"].message,
@@ -281,7 +1188,10 @@ mod tests {
                 "/tests/settings-2.conf"
             ))
             .unwrap();
-        assert_eq!(settings.global.as_ref().unwrap().script_timeout, Some(20));
+        assert_eq!(
+            settings.global.as_ref().unwrap().script_timeout,
+            Some(Duration::from_secs(20))
+        );
         assert_eq!(settings.events.as_ref().unwrap().len(), 2);
         assert_eq!(
             settings.events.as_ref().unwrap()["event-2"].message,
@@ -308,7 +1218,10 @@ mod tests {
                 "/tests/settings-3.conf"
             ))
             .unwrap();
-        assert_eq!(settings.global.as_ref().unwrap().script_timeout, Some(20));
+        assert_eq!(
+            settings.global.as_ref().unwrap().script_timeout,
+            Some(Duration::from_secs(20))
+        );
         assert_eq!(settings.events.as_ref().unwrap().len(), 3);
         assert_eq!(
             settings.events.as_ref().unwrap()["event-3"].message,
@@ -333,7 +1246,10 @@ mod tests {
                 "/tests/settings-4.conf"
             ))
             .unwrap();
-        assert_eq!(settings.global.as_ref().unwrap().script_timeout, Some(10));
+        assert_eq!(
+            settings.global.as_ref().unwrap().script_timeout,
+            Some(Duration::from_secs(10))
+        );
         assert_eq!(settings.events.as_ref().unwrap().len(), 4);
         assert_eq!(
             settings.events.as_ref().unwrap()["event-4"].message,
@@ -364,7 +1280,10 @@ mod tests {
             settings.global.as_ref().unwrap().filters.as_ref().unwrap(),
             &vec!["_TRANSPORT=kernel", "PRIORITY=4"]
         );
-        assert_eq!(settings.global.as_ref().unwrap().script_timeout, Some(10));
+        assert_eq!(
+            settings.global.as_ref().unwrap().script_timeout,
+            Some(Duration::from_secs(10))
+        );
         assert_eq!(settings.events.as_ref().unwrap().len(), 5);
         assert_eq!(
             settings.events.as_ref().unwrap()["xhci_hcd-error"].message,
@@ -3,6 +3,7 @@ mod common;
 use std::{
     io::{BufReader, Seek},
     path::Path,
+    time::Duration,
 };
 
 use journald_broker::script::Script;
@@ -23,7 +24,7 @@ fn missing_jnb_message() {
         "/script-execute-test.sh"
     ));
 
-    let script: Script = Script::new(script_path, Some(20), false).unwrap();
+    let script: Script = Script::new(script_path, Some(Duration::from_secs(20)), false).unwrap();
     let ret = script.run();
     assert!(ret.is_ok(), "Missing JNB_MESSAGE environment variable");
     assert_eq!(
@@ -0,0 +1,92 @@
+mod common;
+
+use std::{
+    fs,
+    io::{BufReader, Seek},
+    path::Path,
+    thread,
+    time::{Duration, Instant},
+};
+
+use journald_broker::script::{EnvVar, Script};
+use tempfile::NamedTempFile;
+
+use crate::common::log_check::{next_log, setup_log};
+
+/// Is process still alive? Killed process may stay a zombie until it is reaped.
+fn is_alive(pid: &str) -> bool {
+    match fs::read_to_string(format!("/proc/{pid}/stat")) {
+        Ok(stat) => stat
+            .rsplit_once(") ")
+            .is_some_and(|(_, state)| !state.starts_with('Z')),
+        Err(_) => false,
+    }
+}
+
+// Script execution timeout, processes started by script are killed with its process group.
+#[test]
+fn script_execution_timeout_kill_process_group() {
+    let mut log_file = setup_log();
+    log_file.seek(std::io::SeekFrom::End(0)).unwrap();
+    let mut reader = BufReader::new(log_file);
+
+    let script_path = Path::new(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests",
+        "/scripts",
+        "/script-execute-test.sh"
+    ));
+    let pid_file = NamedTempFile::new().unwrap();
+
+    let mut script: Script = Script::new(script_path, Some(Duration::from_secs(2)), false).unwrap();
+    script.set_kill(libc::SIGTERM, Duration::from_secs(1));
+
+    script
+        .add_env(EnvVar::Message("SOME ERROR".to_string()))
+        .unwrap();
+
+    script
+        .add_env(EnvVar::Json("SOME JSON".to_string()))
+        .unwrap();
+
+    script
+        .add_env(EnvVar::Custom {
+            key: "SCRIPT_TEST_CASE".to_string(),
+            value: "5".to_string(),
+        })
+        .unwrap();
+
+    script
+        .add_env(EnvVar::Custom {
+            key: "PID_FILE".to_string(),
+            value: pid_file.path().display().to_string(),
+        })
+        .unwrap();
+
+    let ret = script.run();
+    assert_eq!(
+        next_log(&mut reader),
+        format!(
+            " INFO journald_broker::script: Execute `{}`\n",
+            script_path.display()
+        )
+    );
+    assert!(ret.is_err(), "Script execution timeout");
+    assert_eq!(
+        format!("{}", ret.unwrap_err().root_cause()),
+        format!(
+            "Execute timeout `{}`, >= 2 seconds, signal: 9 (SIGKILL)",
+            script_path.display()
+        )
+    );
+
+    // Background process ignores SIGTERM, and it is gone after SIGKILL of process group.
+    let pid = fs::read_to_string(pid_file.path()).unwrap();
+    let pid = pid.trim();
+    assert!(!pid.is_empty(), "Background process is not started");
+    let deadline = Instant::now() + Duration::from_secs(1);
+    while is_alive(pid) && Instant::now() < deadline {
+        thread::sleep(Duration::from_millis(10));
+    }
+    assert!(!is_alive(pid), "Background process {pid} is still running");
+}
@@ -3,6 +3,7 @@ mod common;
 use std::{
     io::{BufReader, Seek},
     path::Path,
+    time::Duration,
 };
 
 use journald_broker::script::{EnvVar, Script};
@@ -23,7 +24,8 @@ fn missing_jnb_json() {
         "/script-execute-test.sh"
     ));
 
-    let mut script: Script = Script::new(script_path, Some(20), false).unwrap();
+    let mut script: Script =
+        Script::new(script_path, Some(Duration::from_secs(20)), false).unwrap();
 
     // Add JNB_MESSAGE env var
     let msg_env = EnvVar::Message("SOME ERROR".to_string());
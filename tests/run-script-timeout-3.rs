@@ -3,6 +3,7 @@ mod common;
 use std::{
     io::{BufReader, Seek},
     path::Path,
+    time::Duration,
 };
 
 use journald_broker::script::{EnvVar, Script};
@@ -18,7 +19,8 @@ fn script_is_not_exist() {
 
     let script_path = Path::new("/tmp/not-exist-script-nowait.sh");
 
-    let mut script: Script = Script::new(script_path, Some(20), false).unwrap();
+    let mut script: Script =
+        Script::new(script_path, Some(Duration::from_secs(20)), false).unwrap();
 
     script
         .add_env(EnvVar::Message("SOME ERROR".to_string()))
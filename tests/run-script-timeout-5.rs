@@ -3,13 +3,14 @@ mod common;
 use std::{
     io::{BufReader, Seek},
     path::Path,
+    time::Duration,
 };
 
 use journald_broker::script::{EnvVar, Script};
 
 use crate::common::log_check::{next_log, setup_log};
 
-// Script execution timeout.
+// Script execution timeout, stop script with SIGTERM before SIGKILL by default.
 #[test]
 fn script_execution_timeout() {
     let mut log_file = setup_log();
@@ -23,7 +24,7 @@ fn script_execution_timeout() {
         "/script-execute-test.sh"
     ));
 
-    let mut script: Script = Script::new(script_path, Some(2), false).unwrap();
+    let mut script: Script = Script::new(script_path, Some(Duration::from_secs(2)), false).unwrap();
 
     script
         .add_env(EnvVar::Message("SOME ERROR".to_string()))
@@ -48,11 +49,25 @@ fn script_execution_timeout() {
             script_path.display()
         )
     );
+    assert_eq!(
+        next_log(&mut reader),
+        format!(
+            " INFO journald_broker::script: Send SIGTERM to process group of `{}`\n",
+            script_path.display()
+        )
+    );
+    assert_eq!(
+        next_log(&mut reader),
+        format!(
+            " INFO journald_broker::script: Send SIGKILL to process group of `{}`\n",
+            script_path.display()
+        )
+    );
     assert!(ret.is_err(), "Script execution timeout");
     assert_eq!(
         format!("{}", ret.unwrap_err().root_cause()),
         format!(
-            "Execute timeout `{}`, >= 2 seconds, signal: 9 (SIGKILL)",
+            "Execute timeout `{}`, >= 2 seconds, signal: 15 (SIGTERM)",
             script_path.display()
         )
     );
@@ -4,6 +4,7 @@ use std::{
     io::{BufReader, Seek},
     path::Path,
     thread,
+    time::Duration,
 };
 
 use journald_broker::script::{EnvVar, Script};
@@ -24,7 +25,7 @@ fn script_normal_exit() {
         "/script-execute-test.sh"
     ));
 
-    let mut script: Script = Script::new(script_path, Some(5), false).unwrap();
+    let mut script: Script = Script::new(script_path, Some(Duration::from_secs(5)), false).unwrap();
 
     script
         .add_env(EnvVar::Message("SOME ERROR".to_string()))
@@ -0,0 +1,63 @@
+mod common;
+
+use std::{
+    io::{BufReader, Seek},
+    path::Path,
+    time::Duration,
+};
+
+use journald_broker::script::{EnvVar, Script};
+
+use crate::common::log_check::{next_log, setup_log};
+
+// Script reads input from stdin.
+#[test]
+fn script_read_input() {
+    let mut log_file = setup_log();
+    log_file.seek(std::io::SeekFrom::End(0)).unwrap();
+    let mut reader = BufReader::new(log_file);
+
+    let script_path = Path::new(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests",
+        "/scripts",
+        "/script-execute-test.sh"
+    ));
+
+    let mut script: Script =
+        Script::new(script_path, Some(Duration::from_secs(20)), false).unwrap();
+
+    script
+        .add_env(EnvVar::Message("SOME ERROR".to_string()))
+        .unwrap();
+
+    script
+        .add_env(EnvVar::Json("SOME JSON".to_string()))
+        .unwrap();
+
+    script
+        .add_env(EnvVar::Custom {
+            key: "SCRIPT_TEST_CASE".to_string(),
+            value: "4".to_string(),
+        })
+        .unwrap();
+
+    script.set_input("{\"count\":2,\"entries\":[]}\n".to_string());
+
+    let ret = script.run();
+    assert!(ret.is_ok(), "Script reads input");
+    assert_eq!(
+        next_log(&mut reader),
+        format!(
+            " INFO journald_broker::script: Execute `{}`\n",
+            script_path.display()
+        )
+    );
+    assert_eq!(
+        next_log(&mut reader),
+        format!(
+            " INFO journald_broker::script: Finished `{}`, exit status: 0\n",
+            script_path.display()
+        )
+    );
+}
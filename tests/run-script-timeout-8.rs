@@ -0,0 +1,47 @@
+mod common;
+
+use std::{
+    io::{BufReader, Seek},
+    path::Path,
+    time::Duration,
+};
+
+use journald_broker::script::Script;
+
+use crate::common::log_check::{next_log, setup_log, wait_for_thread};
+
+// Return without waiting, script is still finished in background
+#[test]
+fn no_wait() {
+    let mut log_file = setup_log();
+    log_file.seek(std::io::SeekFrom::End(0)).unwrap();
+    let mut reader = BufReader::new(log_file);
+
+    let script_path = Path::new(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests",
+        "/scripts",
+        "/script-execute-test.sh"
+    ));
+
+    let mut script: Script =
+        Script::new(script_path, Some(Duration::from_secs(20)), false).unwrap();
+    script.set_wait(false);
+    let ret = script.run();
+    wait_for_thread();
+    assert!(ret.is_ok(), "Run script without waiting");
+    assert_eq!(
+        next_log(&mut reader),
+        format!(
+            " INFO journald_broker::script: Execute `{}`\n",
+            script_path.display()
+        )
+    );
+    assert_eq!(
+        next_log(&mut reader),
+        format!(
+            " INFO journald_broker::script: Finished `{}`, exit status: 51\n",
+            script_path.display()
+        )
+    );
+}
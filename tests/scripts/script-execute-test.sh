@@ -30,4 +30,23 @@ if [[ "$JNB_SCRIPT_TEST_CASE" == "3" ]]; then
     exit 0
 fi
 
+if [[ "$JNB_SCRIPT_TEST_CASE" == "4" ]]; then
+    read -r input
+    if [[ "$input" != '{"count":2,"entries":[]}' ]]; then
+        echo "FAKE-SCRIPT-ERROR: CASE 4 => Unexpected input: $input" >&2
+        exit 53
+    fi
+    exit 0
+fi
+
+if [[ "$JNB_SCRIPT_TEST_CASE" == "5" ]]; then
+    echo "FAKE-SCRIPT-ERROR: CASE 5 => Simulate script timeout with background process" >&2
+    # Background process inherits ignored SIGTERM, only SIGKILL of process group stops it.
+    trap '' TERM
+    sleep 60 &
+    echo "$!" > "$JNB_PID_FILE"
+    wait
+    exit 0
+fi
+
 exit 99